@@ -1,69 +1,234 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs;
+use std::io::Write;
 use std::path::Path;
 use serde::Serialize;
+use serde_json::Value;
 use crate::conf::Conf;
-use crate::error::Error;
-use crate::{dx, monitor};
-use crate::monitor::JobInfo;
-use crate::selection::RunChoice;
+use crate::data::chromosome::Chromosome;
+use crate::error::{DxErrorKind, Error, ErrorKind};
+use crate::{cache, ledger, monitor, validation};
+use crate::apps::spec::{AppSpec, StagedJob};
+use crate::job::{JobState, ResubmitPolicy};
+use crate::ledger::Attempt;
+use crate::monitor::{AppJobs, JobHistory, JobInfo};
+use crate::platform::{AppRun, FileLinks, Platform};
+use crate::selection::{Format, MonitorChoice, RunChoice, TerminateChoice};
 
-pub(crate) mod vcfs2bed;
-pub(crate) mod bed_merge;
+pub(crate) mod spec;
 
-pub(crate) trait JobStaged {
-    const PREFIX: &'static str;
-    fn name(&self) -> String;
-    fn is_name(name: &str) -> bool { name.starts_with(Self::PREFIX) }
+pub(crate) struct AppSettings {
+    pub(crate) instance_type: String,
+    pub(crate) app_path: String,
+    pub(crate) out_dir: String,
+    pub(crate) extra_args: Vec<String>,
+    pub(crate) instance_escalation: Vec<String>,
 }
 
-pub(crate) trait App {
-    type Job: JobStaged;
-    type Inputs: Serialize;
-    const INSTANCE_TYPE: &'static str;
-    const APP_PATH: &'static str;
-    const OUT_DIR_PATH: &'static str;
-    fn create_job_list_unfiltered(conf: &Conf) -> Result<Vec<Self::Job>, Error>;
-    fn create_inputs_definition(job: &Self::Job, conf: &Conf) -> Result<Self::Inputs, Error>;
+fn chromosome_instance_type(app: &AppSpec, chromosome: Chromosome) -> Option<String> {
+    app.chromosome_instance_types.iter().find(|(key, _)| {
+        Chromosome::parse(key).map(|key_chromosome| key_chromosome == chromosome)
+            .unwrap_or(false)
+    }).map(|(_, instance_type)| instance_type.clone())
 }
 
-fn should_be_run(name: &str, jobs: &HashMap<String, JobInfo>) -> bool {
-    match jobs.get(name) {
+pub(crate) fn app_settings(app: &AppSpec, chromosome: Option<Chromosome>) -> AppSettings {
+    let instance_type =
+        chromosome.and_then(|chromosome| { chromosome_instance_type(app, chromosome) })
+            .unwrap_or(app.instance_type.clone());
+    let app_path = app.app_path.clone();
+    let out_dir = app.out_dir.clone();
+    let extra_args = app.extra_args.clone();
+    let instance_escalation = app.instance_escalation.clone();
+    AppSettings { instance_type, app_path, out_dir, extra_args, instance_escalation }
+}
+
+pub(crate) fn should_be_run(name: &str, jobs: &HashMap<String, JobHistory>,
+                 attempts: &HashMap<String, Vec<Attempt>>, policy: &ResubmitPolicy) -> bool {
+    match jobs.get(name).map(|history| &history.latest) {
+        None => { true }
+        Some(job) => {
+            let n_attempts = attempts.get(name).map(|attempts| attempts.len()).unwrap_or(1);
+            let needs_to_be_submitted = policy.needs_to_be_submitted(job.state);
+            if needs_to_be_submitted && !policy.allows_attempt(n_attempts) {
+                println!("Not resubmitting {}, which used {} of {} attempts.", name, n_attempts,
+                         policy.max_attempts());
+            }
+            needs_to_be_submitted && policy.allows_attempt(n_attempts)
+        }
+    }
+}
+
+pub(crate) fn passes_pat(name: &str, pat: &Option<String>) -> bool {
+    match pat {
         None => { true }
-        Some(job) => { job.state.needs_to_be_submitted() }
+        Some(pat) => { name.contains(pat) }
+    }
+}
+
+fn should_be_rerun(name: &str, jobs: &HashMap<String, JobHistory>) -> bool {
+    match jobs.get(name).map(|history| &history.latest) {
+        Some(job) if job.state.is_active() && job.state != JobState::Restartable => {
+            println!("Not rerunning {}, which is {}.", name, job.state);
+            false
+        }
+        _ => { true }
+    }
+}
+
+fn restartable_job<'a>(name: &str, jobs: &'a HashMap<String, JobHistory>)
+                       -> Option<&'a JobInfo> {
+    jobs.get(name).map(|history| &history.latest)
+        .filter(|job| job.state == JobState::Restartable)
+}
+
+pub(crate) fn terminate_restartable(platform: &dyn Platform, name: &str,
+                                    jobs: &HashMap<String, JobHistory>) -> Result<(), Error> {
+    if let Some(job) = restartable_job(name, jobs) {
+        platform.terminate_job(&job.id)?;
+        println!("Terminated {} ({}), which was restartable, to submit it again.", name, job.id);
+    }
+    Ok(())
+}
+
+pub(crate) fn print_dry_terminate(name: &str, jobs: &HashMap<String, JobHistory>) {
+    if let Some(job) = restartable_job(name, jobs) {
+        println!("This would terminate {} ({}), which is restartable", name, job.id)
     }
 }
 
-fn filter_job_list<J: JobStaged>(jobs_unfiltered: Vec<J>, conf: &Conf, pat: &Option<String>)
-                                 -> Result<Vec<J>, Error> {
-    let mut jobs: Vec<J> = Vec::new();
-    let submitted_jobs = monitor::jobs_by_name(conf)?;
+fn read_names(file: &str) -> Result<HashSet<String>, Error> {
+    let names =
+        fs::read_to_string(file)?.lines().map(|line| line.trim())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| line.to_string()).collect();
+    Ok(names)
+}
+
+fn filter_job_list(jobs_unfiltered: Vec<StagedJob>, conf: &Conf,
+                   submitted_jobs: &HashMap<String, JobHistory>,
+                   attempts: &HashMap<String, Vec<Attempt>>, pat: &Option<String>,
+                   names: &Option<HashSet<String>>) -> Result<Vec<StagedJob>, Error> {
+    let mut jobs: Vec<StagedJob> = Vec::new();
+    let policy = ResubmitPolicy::from_conf(conf)?;
     for job in jobs_unfiltered {
-        let passes_pat =
-            match pat {
-                None => { true }
-                Some(pat) => { job.name().contains(pat) }
+        let selected =
+            passes_pat(&job.name, pat) && match names {
+                None => { should_be_run(&job.name, submitted_jobs, attempts, &policy) }
+                Some(names) => {
+                    names.contains(&job.name) && should_be_rerun(&job.name, submitted_jobs)
+                }
             };
-        if passes_pat && should_be_run(&job.name(), &submitted_jobs) {
+        if selected {
             jobs.push(job)
         }
     }
     Ok(jobs)
 }
 
-pub(crate) fn run_jobs<A: App>(conf: &Conf, run: &RunChoice) -> Result<(), Error> {
+fn escalation_rank(instance_escalation: &[String], instance_type: &str) -> Option<usize> {
+    instance_escalation.iter().position(|escalated| escalated == instance_type)
+}
+
+pub(crate) fn instance_type_for(app: &AppSpec, name: &str, chromosome: Chromosome,
+                                submitted_jobs: &HashMap<String, JobHistory>,
+                                attempts: &HashMap<String, Vec<Attempt>>) -> String {
+    let settings = app_settings(app, Some(chromosome));
+    let escalation = &settings.instance_escalation;
+    let previous =
+        attempts.get(name).and_then(|attempts| attempts.last())
+            .map(|attempt| attempt.instance_type.clone());
+    let instance_type =
+        match previous {
+            Some(previous) if escalation_rank(escalation, &previous)
+                > escalation_rank(escalation, &settings.instance_type) => { previous }
+            _ => { settings.instance_type }
+        };
+    let needs_larger_instance =
+        submitted_jobs.get(name).and_then(|history| history.latest.failure_cause())
+            .map(|cause| cause.needs_larger_instance()).unwrap_or(false);
+    if needs_larger_instance {
+        let next =
+            match escalation_rank(escalation, &instance_type) {
+                None => { escalation.first() }
+                Some(rank) => { escalation.get(rank + 1) }
+            };
+        match next {
+            Some(next) if *next != instance_type => {
+                println!("Escalating {} from {} to {}.", name, instance_type, next);
+                next.clone()
+            }
+            _ => { instance_type }
+        }
+    } else {
+        instance_type
+    }
+}
+
+pub(crate) fn check_unledgered(app: &AppSpec, app_jobs: &AppJobs) -> Result<(), Error> {
+    let n_unledgered = app_jobs.unledgered.len();
+    if n_unledgered > 0 && app_jobs.ledgered.is_empty() {
+        Err(Error::from(format!("Found {} {} jobs on the platform, but none in the job ledger. \
+            Run 'udix {} reconcile' to adopt them before submitting.", n_unledgered, app.name,
+                                app.name)))?
+    }
+    if n_unledgered > 0 {
+        println!("Ignoring {} {} jobs on the platform that are not in the job ledger. \
+            See 'udix {} reconcile'.", n_unledgered, app.name, app.name);
+    }
+    Ok(())
+}
+
+pub(crate) fn run_jobs(conf: &Conf, platform: &dyn Platform, app: &AppSpec, run: &RunChoice)
+                       -> Result<(), Error> {
+    validation::check_for_app(conf, platform, app)?;
     let pat = &run.pat;
-    let jobs_unfiltered = A::create_job_list_unfiltered(conf)?;
-    let mut jobs = filter_job_list(jobs_unfiltered, conf, pat)?;
+    let jobs_unfiltered = app.create_job_list_unfiltered(conf, platform)?;
+    let app_jobs = monitor::find_app_jobs(conf, platform, app)?;
+    check_unledgered(app, &app_jobs)?;
+    let submitted_jobs = monitor::by_name(app_jobs.ledgered);
+    let attempts = ledger::attempts_by_name(conf, &app.name)?;
+    let names = run.names.as_deref().map(read_names).transpose()?;
+    if let Some(names) = &names {
+        let mut unknown: Vec<&String> =
+            names.iter().filter(|name| {
+                !jobs_unfiltered.iter().any(|job| job.name == **name)
+            }).collect();
+        unknown.sort();
+        for name in unknown {
+            println!("Ignoring {}, which is not a {} job.", name, app.name)
+        }
+    }
+    let mut jobs =
+        filter_job_list(jobs_unfiltered, conf, &submitted_jobs, &attempts, pat, &names)?;
     if let Some(num) = run.num {
         jobs.truncate(num)
     }
-    for job in jobs {
-        if run.dry {
-            println!("This would run {}", job.name())
-        } else {
-            run_job::<A>(&job, conf)?;
+    if run.dry {
+        for job in jobs {
+            let instance_type =
+                instance_type_for(app, &job.name, job.chromosome, &submitted_jobs,
+                                  &attempts);
+            print_dry_terminate(&job.name, &submitted_jobs);
+            println!("This would run {} on {}", job.name, instance_type)
+        }
+    } else if !jobs.is_empty() {
+        let names: Vec<String> = jobs.iter().flat_map(StagedJob::input_file_names).collect();
+        let links =
+            cache::resolve_links(conf, platform, &conf.in_project(app.inputs_folder(conf)),
+                                 &names)?;
+        let mut batch = BatchSubmission::new();
+        for job in jobs {
+            let instance_type =
+                instance_type_for(app, &job.name, job.chromosome, &submitted_jobs,
+                                  &attempts);
+            let result =
+                terminate_restartable(platform, &job.name, &submitted_jobs).and_then(|()| {
+                    run_job(conf, platform, app, &job, &links, instance_type)
+                });
+            batch.add(&job.name, result)?;
         }
+        batch.finish(&[app])?;
     }
     Ok(())
 }
@@ -74,41 +239,250 @@ fn write_inputs_definition<I: Serialize>(file: &Path, inputs: &I) -> Result<(),
     Ok(())
 }
 
-fn inputs_file_name<J: JobStaged>(job: &J) -> String {
-    format!("inputs_{}", job.name())
+fn inputs_file_name(name: &str) -> String {
+    format!("inputs_{}", name)
 }
 
-pub(crate) fn monitor_jobs<J: JobStaged>(conf: &Conf) -> Result<(), Error> {
-    let jobs = monitor::find_jobs(conf)?;
-    for job in jobs {
-        if J::is_name(&job.name) {
-            println!("Job {} is {}", job.name, job.state);
+pub(crate) fn monitor_jobs(conf: &Conf, platform: &dyn Platform, app: &AppSpec,
+                           monitor_choice: &MonitorChoice, format: Format) -> Result<(), Error> {
+    let expected = app.create_job_list_unfiltered(conf, platform)?;
+    if monitor_choice.watch {
+        if format != Format::Text {
+            Err(Error::from("Watch mode only supports the text format."))?
         }
+        monitor::watch_jobs(conf, platform, app, &expected, monitor_choice.interval)
+    } else {
+        let submitted_jobs = monitor::jobs_by_name(conf, platform, app)?;
+        let summary = monitor::Summary::new(app, &expected, &submitted_jobs);
+        summary.print_as(format)?;
+        summary.check_duplicates()
     }
+}
+
+pub(crate) fn show_log(conf: &Conf, platform: &dyn Platform, app: &AppSpec, name: &str)
+                       -> Result<(), Error> {
+    if !app.is_name(name) {
+        Err(Error::from(format!("{} is not a {} job name.", name, app.name)))?
+    }
+    let attempts = ledger::attempts_by_name(conf, &app.name)?;
+    let attempt =
+        attempts.get(name).and_then(|attempts| attempts.last()).ok_or_else(|| {
+            Error::from(format!("No job named {} in the job ledger.", name))
+        })?;
+    println!("Log of job {} ({}):", name, attempt.job_id);
+    print!("{}", platform.job_log(&attempt.job_id)?);
     Ok(())
 }
 
-fn run_job<A: App>(job: &A::Job, conf: &Conf) -> Result<(), Error> {
-    let inputs = A::create_inputs_definition(job, conf)?;
+fn confirm(question: &str) -> Result<bool, Error> {
+    print!("{} [y/N] ", question);
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+pub(crate) fn terminate_jobs(conf: &Conf, platform: &dyn Platform, app: &AppSpec,
+                             terminate: &TerminateChoice) -> Result<(), Error> {
+    let states =
+        if terminate.states.is_empty() {
+            JobState::ALL.iter().filter(|state| state.is_active()).copied().collect()
+        } else {
+            terminate.states.iter().map(|state| JobState::try_from(state.as_str()))
+                .collect::<Result<Vec<JobState>, Error>>()?
+        };
+    let jobs: Vec<JobInfo> =
+        monitor::find_app_jobs(conf, platform, app)?.ledgered.into_iter().filter(|job| {
+            passes_pat(&job.name, &terminate.pat) && states.contains(&job.state)
+        }).collect();
+    if terminate.dry {
+        for job in &jobs {
+            println!("This would terminate {} ({}), which is {}", job.name, job.id, job.state)
+        }
+        return Ok(());
+    }
+    if jobs.is_empty() {
+        println!("No matching jobs to terminate.");
+        return Ok(());
+    }
+    let threshold = conf.jobs.confirm_terminate_above;
+    if jobs.len() > threshold && !terminate.yes
+        && !confirm(&format!("Terminate {} jobs?", jobs.len()))? {
+        println!("Not terminating any jobs.");
+        return Ok(());
+    }
+    for job in &jobs {
+        platform.terminate_job(&job.id)?;
+        println!("Terminated {} ({}), which was {}.", job.name, job.id, job.state);
+    }
+    Ok(())
+}
+
+pub(crate) fn run_job(conf: &Conf, platform: &dyn Platform, app: &AppSpec, job: &StagedJob,
+                      links: &FileLinks, instance_type: String) -> Result<String, Error> {
+    let inputs = app.create_inputs_definition(job, links)?;
+    submit_job(conf, platform, app, job.name.clone(), job.chromosome, &inputs, instance_type)
+}
+
+pub(crate) fn submit_job(conf: &Conf, platform: &dyn Platform, app: &AppSpec, name: String,
+                         chromosome: Chromosome, inputs: &Value, instance_type: String)
+                         -> Result<String, Error> {
     let work_dir_string = conf.workspace.work_dir_fixed()?;
     let work_dir = Path::new(&work_dir_string);
     fs::create_dir_all(work_dir)?;
-    let name = job.name();
-    let inputs_file = work_dir.join(inputs_file_name(job));
-    write_inputs_definition(&inputs_file, &inputs)?;
+    let inputs_file = work_dir.join(inputs_file_name(&name));
+    write_inputs_definition(&inputs_file, inputs)?;
     println!("Next job to run is {}", name);
-    let inputs_file_arg =
-        inputs_file.to_str().ok_or_else(|| {
-            Error::from(format!("Could not convert file path '{}' to string.",
-                                inputs_file.to_string_lossy()))
-        })?;
-    let folder_arg = format!("{}:{}", dx::get_project()?, A::OUT_DIR_PATH);
-    dx::run(&["run", "--name", name.as_str(), "--input-json-file", inputs_file_arg,
-        "--folder", folder_arg.as_str(), "--instance-type", A::INSTANCE_TYPE,
-        A::APP_PATH])?;
-    println!("Launched job {} with inputs definition file {}.", name, inputs_file_arg);
-    Ok(())
+    let settings = app_settings(app, Some(chromosome));
+    let folder = format!("{}:{}", conf.project(platform)?, settings.out_dir);
+    let app_run = AppRun {
+        name: name.as_str(),
+        inputs_file: &inputs_file,
+        folder: folder.as_str(),
+        instance_type: instance_type.as_str(),
+        app_path: settings.app_path.as_str(),
+        extra_args: &settings.extra_args,
+    };
+    let job_id = platform.run_app(&app_run)?;
+    println!("Launched job {} ({}) with inputs definition file {}.", name, job_id,
+             inputs_file.to_string_lossy());
+    let attempt = Attempt::new(job_id.clone(), name, &app.name, instance_type, &inputs_file)?;
+    ledger::record(conf, &attempt)?;
+    Ok(job_id)
 }
 
+pub(crate) struct BatchSubmission {
+    n_submitted: usize,
+    failed: Vec<String>,
+}
 
+fn affects_only_one_job(error: &Error) -> bool {
+    matches!(error.kind(),
+        ErrorKind::Dx(DxErrorKind::Network | DxErrorKind::RateLimited | DxErrorKind::Other))
+}
 
+impl BatchSubmission {
+    pub(crate) fn new() -> BatchSubmission {
+        BatchSubmission { n_submitted: 0, failed: Vec::new() }
+    }
+    pub(crate) fn add(&mut self, name: &str, result: Result<String, Error>)
+                      -> Result<Option<String>, Error> {
+        match result {
+            Ok(job_id) => {
+                self.n_submitted += 1;
+                Ok(Some(job_id))
+            }
+            Err(error) if affects_only_one_job(&error) => {
+                println!("Failed to submit {}: {}", name, error);
+                self.failed.push(name.to_string());
+                Ok(None)
+            }
+            Err(error) => {
+                println!("Stopped after submitting {} jobs.", self.n_submitted);
+                self.report_failed();
+                Err(error)
+            }
+        }
+    }
+    fn report_failed(&self) {
+        if !self.failed.is_empty() {
+            println!("Failed to submit {} jobs: {}", self.failed.len(), self.failed.join(", "))
+        }
+    }
+    pub(crate) fn finish(self, apps: &[&AppSpec]) -> Result<(), Error> {
+        if self.failed.is_empty() {
+            Ok(())
+        } else {
+            self.report_failed();
+            let reconcile_commands: Vec<String> =
+                apps.iter().map(|app| format!("'udix {} reconcile'", app.name)).collect();
+            Err(Error::from(format!("Submitted {} jobs, but failed to submit {}. A failed \
+                submission may still have created a job, so run {} before submitting them \
+                again.", self.n_submitted, self.failed.len(), reconcile_commands.join(" and "))))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use serde_json::json;
+    use crate::apps::spec::find_spec;
+    use crate::ledger;
+    use crate::monitor::{self, JobInfo};
+    use crate::platform::fake::FakePlatform;
+    use crate::platform::{JobQuery, Platform};
+    use crate::selection::RunChoice;
+    use crate::testing::{cassette_conf, replay_cassette, TempDir};
+    use super::{run_jobs, should_be_rerun, terminate_restartable};
+
+    #[test]
+    fn replayed_runs_submit_each_job_once_and_resubmit_failures() {
+        let work_dir = TempDir::new("replayed_runs");
+        let conf = cassette_conf("/udix/beds/", &work_dir.path);
+        let platform = replay_cassette("vcfs2bed_run.jsonl");
+        let app = find_spec(&conf, "vcfs2bed").unwrap();
+        let run = RunChoice { num: None, dry: false, pat: None, names: None };
+        let ledgered_names = || -> Vec<String> {
+            ledger::read_attempts(&conf).unwrap().into_iter().map(|attempt| attempt.name)
+                .collect()
+        };
+        run_jobs(&conf, &platform, &app, &run).unwrap();
+        assert_eq!(ledgered_names(), vec!["vcfs2bed_c1_b0", "vcfs2bed_c1_b1", "vcfs2bed_c2_b0",
+            "vcfs2bed_c10_b0", "vcfs2bed_cX_b0"]);
+        run_jobs(&conf, &platform, &app, &run).unwrap();
+        assert_eq!(ledgered_names().len(), 5);
+        run_jobs(&conf, &platform, &app, &run).unwrap();
+        let attempts = ledger::read_attempts(&conf).unwrap();
+        assert_eq!(attempts.len(), 6);
+        assert_eq!(attempts[5].name, "vcfs2bed_c1_b1");
+        assert_eq!(attempts[5].job_id, "job-GbZ505QJK4Fk2v8Bz1xQ7pJ");
+    }
+
+    #[test]
+    fn replayed_run_continues_past_a_failed_submission() {
+        let work_dir = TempDir::new("replayed_flaky_run");
+        let conf = cassette_conf("/udix/beds/", &work_dir.path);
+        let platform = replay_cassette("vcfs2bed_run_flaky.jsonl");
+        let app = find_spec(&conf, "vcfs2bed").unwrap();
+        let run = RunChoice { num: None, dry: false, pat: None, names: None };
+        let error = run_jobs(&conf, &platform, &app, &run).err().unwrap();
+        assert!(error.to_string().contains("Submitted 4 jobs, but failed to submit 1"),
+                "{}", error);
+        let ledgered_names: Vec<String> =
+            ledger::read_attempts(&conf).unwrap().into_iter().map(|attempt| attempt.name)
+                .collect();
+        assert_eq!(ledgered_names, vec!["vcfs2bed_c1_b0", "vcfs2bed_c1_b1", "vcfs2bed_c10_b0",
+            "vcfs2bed_cX_b0"]);
+    }
+
+    #[test]
+    fn restartable_jobs_are_terminated_before_they_are_submitted_again() {
+        let root = TempDir::new("terminate_restartable");
+        let jobs = json!([
+            {"id": "job-1", "name": "vcfs2bed_c1_b0", "state": "restartable", "created": 1,
+                "project": "project-fake"},
+            {"id": "job-2", "name": "vcfs2bed_c1_b1", "state": "running", "created": 2,
+                "project": "project-fake"},
+        ]);
+        fs::create_dir_all(&root.path).unwrap();
+        fs::write(root.path.join("jobs.json"), jobs.to_string()).unwrap();
+        let platform = FakePlatform::new(root.path.clone());
+        let job_infos: Vec<JobInfo> = serde_json::from_value(jobs).unwrap();
+        let by_name = monitor::by_name(job_infos);
+        assert!(should_be_rerun("vcfs2bed_c1_b0", &by_name));
+        assert!(!should_be_rerun("vcfs2bed_c1_b1", &by_name));
+        terminate_restartable(&platform, "vcfs2bed_c1_b0", &by_name).unwrap();
+        terminate_restartable(&platform, "vcfs2bed_c1_b1", &by_name).unwrap();
+        let query =
+            JobQuery { created_after: 0, project: "project-fake", name_prefix: None,
+                launched_by: None };
+        let mut states: Vec<(String, String)> = Vec::new();
+        platform.find_jobs(&query, &mut |job| {
+            states.push((job.id, job.state.to_string()));
+            Ok(())
+        }).unwrap();
+        assert_eq!(states, vec![("job-1".to_string(), "terminated".to_string()),
+                                ("job-2".to_string(), "running".to_string())]);
+    }
+}
@@ -0,0 +1,355 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use toml::Table;
+use crate::conf::Conf;
+use crate::data::beds::{get_bed_bundles_by_chrom, FileType};
+use crate::data::chromosome::Chromosome;
+use crate::data::vcfs::group_vcf_files;
+use crate::error::Error;
+use crate::platform::{FileLinks, Platform, WrappedDnaNexusLink};
+
+pub(crate) mod builtin {
+    pub(crate) const VCFS2BED: &str = "vcfs2bed";
+    pub(crate) const BED_MERGE: &str = "bed_merge";
+}
+
+const BUILTIN_SPECS: &str = include_str!("builtin_specs.toml");
+
+const BED_BUNDLE_SOURCES: [(InputSource, FileType); 3] =
+    [(InputSource::Bed, FileType::Bed), (InputSource::Bim, FileType::Bim),
+        (InputSource::Fam, FileType::Fam)];
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Sharding {
+    VcfBlock,
+    VcfChromosome,
+    BedBundleSet,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum InputSource {
+    Vcf,
+    Bed,
+    Bim,
+    Fam,
+    JobName,
+}
+
+#[derive(Deserialize, Clone)]
+pub(crate) struct AppSpec {
+    #[serde(skip)]
+    pub(crate) name: String,
+    pub(crate) prefix: String,
+    pub(crate) instance_type: String,
+    pub(crate) app_path: String,
+    pub(crate) out_dir: String,
+    pub(crate) sharding: Sharding,
+    pub(crate) inputs: BTreeMap<String, InputSource>,
+    #[serde(default)]
+    pub(crate) outputs: BTreeMap<String, InputSource>,
+    #[serde(default)]
+    pub(crate) extra_args: Vec<String>,
+    #[serde(default)]
+    pub(crate) chromosome_instance_types: BTreeMap<String, String>,
+    #[serde(default)]
+    pub(crate) instance_escalation: Vec<String>,
+}
+
+pub(crate) struct StagedJob {
+    pub(crate) name: String,
+    pub(crate) chromosome: Chromosome,
+    pub(crate) block: Option<usize>,
+    files: BTreeMap<InputSource, Vec<String>>,
+}
+
+impl Sharding {
+    pub(crate) fn provides(&self, source: InputSource) -> bool {
+        match self {
+            Sharding::VcfBlock | Sharding::VcfChromosome => {
+                matches!(source, InputSource::Vcf | InputSource::JobName)
+            }
+            Sharding::BedBundleSet => { source != InputSource::Vcf }
+        }
+    }
+    pub(crate) fn inputs_folder_key(&self) -> &'static str {
+        match self {
+            Sharding::VcfBlock | Sharding::VcfChromosome => { "data.vcfs_dir" }
+            Sharding::BedBundleSet => { "data.beds_dir" }
+        }
+    }
+}
+
+impl InputSource {
+    pub(crate) fn file_extension(&self) -> Option<&'static str> {
+        match self {
+            InputSource::Vcf => { Some("vcf.gz") }
+            InputSource::Bed => { Some("bed") }
+            InputSource::Bim => { Some("bim") }
+            InputSource::Fam => { Some("fam") }
+            InputSource::JobName => { None }
+        }
+    }
+}
+
+impl Display for Sharding {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Sharding::VcfBlock => { write!(f, "vcf_block") }
+            Sharding::VcfChromosome => { write!(f, "vcf_chromosome") }
+            Sharding::BedBundleSet => { write!(f, "bed_bundle_set") }
+        }
+    }
+}
+
+impl Display for InputSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InputSource::Vcf => { write!(f, "vcf") }
+            InputSource::Bed => { write!(f, "bed") }
+            InputSource::Bim => { write!(f, "bim") }
+            InputSource::Fam => { write!(f, "fam") }
+            InputSource::JobName => { write!(f, "job_name") }
+        }
+    }
+}
+
+impl StagedJob {
+    pub(crate) fn input_file_names(&self) -> Vec<String> {
+        self.files.values().flatten().cloned().collect()
+    }
+}
+
+impl AppSpec {
+    pub(crate) fn job_name(&self, chromosome: Chromosome, block: Option<usize>) -> String {
+        match block {
+            None => { format!("{}_c{}", self.prefix, chromosome) }
+            Some(block) => { format!("{}_c{}_b{}", self.prefix, chromosome, block) }
+        }
+    }
+    pub(crate) fn is_name(&self, name: &str) -> bool { name.starts_with(&self.prefix) }
+    pub(crate) fn parse_chromosome(&self, name: &str) -> Option<Chromosome> {
+        let mut parts = name.strip_prefix(&self.prefix)?.strip_prefix('_')?.split('_');
+        let chromosome = Chromosome::parse(parts.next()?).ok()?;
+        if let Some(part) = parts.next() {
+            part.strip_prefix('b')?.parse::<usize>().ok()?;
+        }
+        if parts.next().is_some() {
+            None
+        } else {
+            Some(chromosome)
+        }
+    }
+    pub(crate) fn output_file_names(&self, job_name: &str) -> Vec<String> {
+        self.outputs.values().filter_map(|source| source.file_extension())
+            .map(|extension| format!("{}.{}", job_name, extension)).collect()
+    }
+    pub(crate) fn inputs_folder<'a>(&self, conf: &'a Conf) -> &'a str {
+        match self.sharding {
+            Sharding::VcfBlock | Sharding::VcfChromosome => { conf.data.vcfs_dir.as_str() }
+            Sharding::BedBundleSet => { conf.data.beds_dir.as_str() }
+        }
+    }
+    fn staged_job(&self, chromosome: Chromosome, block: Option<usize>,
+                  files: BTreeMap<InputSource, Vec<String>>) -> StagedJob {
+        let name = self.job_name(chromosome, block);
+        StagedJob { name, chromosome, block, files }
+    }
+    pub(crate) fn create_job_list_unfiltered(&self, conf: &Conf, platform: &dyn Platform)
+                                             -> Result<Vec<StagedJob>, Error> {
+        let mut jobs: Vec<StagedJob> = Vec::new();
+        match self.sharding {
+            Sharding::VcfBlock => {
+                for vcf_files_of_chr in group_vcf_files(conf, platform)? {
+                    let chromosome = vcf_files_of_chr.chromosome;
+                    for block in vcf_files_of_chr.blocks {
+                        let names = block.files.into_iter().map(|file| file.name).collect();
+                        let files = BTreeMap::from([(InputSource::Vcf, names)]);
+                        jobs.push(self.staged_job(chromosome, Some(block.i_block), files))
+                    }
+                }
+            }
+            Sharding::VcfChromosome => {
+                for vcf_files_of_chr in group_vcf_files(conf, platform)? {
+                    let names =
+                        vcf_files_of_chr.blocks.into_iter().flat_map(|block| block.files)
+                            .map(|file| file.name).collect();
+                    let files = BTreeMap::from([(InputSource::Vcf, names)]);
+                    jobs.push(self.staged_job(vcf_files_of_chr.chromosome, None, files))
+                }
+            }
+            Sharding::BedBundleSet => {
+                for bed_bundles_of_chr in get_bed_bundles_by_chrom(conf, platform)? {
+                    let mut files: BTreeMap<InputSource, Vec<String>> = BTreeMap::new();
+                    for bed_bundle in &bed_bundles_of_chr.bed_bundles {
+                        for (source, file_type) in BED_BUNDLE_SOURCES {
+                            let file_name = bed_bundle.file_name(&file_type);
+                            files.entry(source).or_default().push(file_name)
+                        }
+                    }
+                    jobs.push(self.staged_job(bed_bundles_of_chr.chromosome, None, files))
+                }
+            }
+        }
+        Ok(jobs)
+    }
+    fn fill_inputs(&self, name: &str,
+                   links_for: &dyn Fn(InputSource) -> Result<Vec<WrappedDnaNexusLink>, Error>)
+                   -> Result<Value, Error> {
+        let mut inputs = Map::new();
+        for (field, source) in &self.inputs {
+            let value =
+                match source {
+                    InputSource::JobName => { Value::String(name.to_string()) }
+                    _ => { serde_json::to_value(links_for(*source)?)? }
+                };
+            inputs.insert(field.clone(), value);
+        }
+        Ok(Value::Object(inputs))
+    }
+    pub(crate) fn create_inputs_definition(&self, job: &StagedJob, links: &FileLinks)
+                                           -> Result<Value, Error> {
+        self.fill_inputs(&job.name, &|source| {
+            job.files.get(&source).into_iter().flatten()
+                .map(|file_name| links.get_wrapped(file_name)).collect()
+        })
+    }
+    pub(crate) fn inputs_from_jobs(&self, name: &str, upstream: &AppSpec, job_ids: &[String])
+                                   -> Result<Value, Error> {
+        self.fill_inputs(name, &|source| {
+            let field =
+                upstream.outputs.iter().find(|(_, output_source)| **output_source == source)
+                    .map(|(field, _)| field).ok_or_else(|| {
+                    Error::from(format!("App {} has no {} output needed by app {}.",
+                                        upstream.name, source, self.name))
+                })?;
+            Ok(job_ids.iter().map(|job_id| WrappedDnaNexusLink::job_output(job_id, field))
+                .collect())
+        })
+    }
+}
+
+fn override_table(table: &mut Table, overrides: &Table) {
+    for (key, value) in overrides {
+        match (table.get_mut(key), value) {
+            (Some(toml::Value::Table(table)), toml::Value::Table(overrides)) => {
+                override_table(table, overrides)
+            }
+            _ => { table.insert(key.clone(), value.clone()); }
+        }
+    }
+}
+
+pub(crate) fn all_specs(conf: &Conf) -> Result<BTreeMap<String, AppSpec>, Error> {
+    let mut tables: Table = BUILTIN_SPECS.parse()?;
+    override_table(&mut tables, &conf.specs);
+    let mut specs: BTreeMap<String, AppSpec> = BTreeMap::new();
+    for (name, table) in tables {
+        let mut spec: AppSpec =
+            table.try_into().map_err(|error| {
+                Error::from(format!("Invalid spec for app {}: {}", name, error))
+            })?;
+        spec.name = name.clone();
+        specs.insert(name, spec);
+    }
+    Ok(specs)
+}
+
+pub(crate) fn find_spec(conf: &Conf, name: &str) -> Result<AppSpec, Error> {
+    let mut specs = all_specs(conf)?;
+    match specs.remove(name) {
+        None => {
+            let names: Vec<&str> = specs.keys().map(|name| name.as_str()).collect();
+            Err(Error::from(format!("Unknown app {}. Known apps are {}.", name,
+                                    names.join(", "))))
+        }
+        Some(spec) => { Ok(spec) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use crate::data::chromosome::Chromosome;
+    use crate::testing::{cassette_conf, replay_cassette};
+    use crate::apps::app_settings;
+    use super::{all_specs, builtin, find_spec, AppSpec};
+
+    fn builtin_spec(name: &str) -> AppSpec {
+        find_spec(&cassette_conf("/udix/beds/", Path::new("/nonexistent")), name).unwrap()
+    }
+
+    fn chromosome(name: &str) -> Option<Chromosome> {
+        Some(Chromosome::parse(name).unwrap())
+    }
+
+    #[test]
+    fn parse_chromosome_of_block_jobs() {
+        let app = builtin_spec(builtin::VCFS2BED);
+        assert_eq!(app.parse_chromosome("vcfs2bed_c1_b0"), chromosome("1"));
+        assert_eq!(app.parse_chromosome("vcfs2bed_c22_b17"), chromosome("22"));
+        assert_eq!(app.parse_chromosome("vcfs2bed_cX_b3"), chromosome("X"));
+        assert_eq!(app.parse_chromosome("vcfs2bed_c1"), chromosome("1"));
+    }
+
+    #[test]
+    fn parse_chromosome_rejects_other_names() {
+        let app = builtin_spec(builtin::VCFS2BED);
+        for name in ["bedmerge_c1", "vcfs2bed", "vcfs2bedx_c1_b0", "vcfs2bed_c1_bx",
+            "vcfs2bed_c1_x0", "vcfs2bed_c1_b0_b1", "vcfs2bed_cZ_b0"] {
+            assert_eq!(app.parse_chromosome(name), None, "{}", name);
+        }
+    }
+
+    #[test]
+    fn job_names_round_trip() {
+        let app = builtin_spec(builtin::BED_MERGE);
+        let name = app.job_name(Chromosome::parse("X").unwrap(), None);
+        assert_eq!(name, "bedmerge_cX");
+        assert_eq!(app.parse_chromosome(&name), chromosome("X"));
+        assert_eq!(app.output_file_names(&name),
+                   vec!["bedmerge_cX.bed", "bedmerge_cX.bim", "bedmerge_cX.fam"]);
+    }
+
+    #[test]
+    fn stage_recorded_bed_bundle_sets_per_chromosome() {
+        let conf = cassette_conf("/udix/beds/", Path::new("/nonexistent"));
+        let app = find_spec(&conf, builtin::BED_MERGE).unwrap();
+        let platform = replay_cassette("bed_folder.jsonl");
+        let jobs = app.create_job_list_unfiltered(&conf, &platform).unwrap();
+        let names: Vec<&str> = jobs.iter().map(|job| job.name.as_str()).collect();
+        assert_eq!(names, vec!["bedmerge_c1", "bedmerge_c2", "bedmerge_c10", "bedmerge_cX"]);
+        assert_eq!(jobs[0].input_file_names().len(), 6);
+        assert_eq!(jobs[1].input_file_names().len(), 3);
+    }
+
+    #[test]
+    fn config_spec_sections_are_merged_over_the_built_in_specs() {
+        let mut conf = cassette_conf("/udix/beds", Path::new("/tmp/udix"));
+        conf.specs = toml::from_str(r#"
+[vcfs2bed]
+instance_type = "mem2_ssd1_v2_x8"
+[vcfs2bed.chromosome_instance_types]
+1 = "mem3_ssd1_v2_x16"
+"#).unwrap();
+        let original = builtin_spec(builtin::VCFS2BED);
+        let spec = find_spec(&conf, builtin::VCFS2BED).unwrap();
+        assert_eq!(spec.instance_type, "mem2_ssd1_v2_x8");
+        assert_eq!(spec.app_path, original.app_path);
+        assert!(spec.inputs == original.inputs);
+        let settings = app_settings(&spec, Some(Chromosome::parse("1").unwrap()));
+        assert_eq!(settings.instance_type, "mem3_ssd1_v2_x16");
+        assert_eq!(all_specs(&conf).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn incomplete_new_spec_is_an_error() {
+        let mut conf = cassette_conf("/udix/beds", Path::new("/tmp/udix"));
+        conf.specs = toml::from_str("[chrbed]\nprefix = \"chrbed\"\n").unwrap();
+        let error = all_specs(&conf).err().unwrap();
+        assert!(error.to_string().contains("Invalid spec for app chrbed"));
+    }
+}
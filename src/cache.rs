@@ -0,0 +1,235 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+use std::time::SystemTime;
+use serde::{Deserialize, Serialize};
+use crate::conf::Conf;
+use crate::error::Error;
+use crate::platform::{DnaNexusLink, FileInfo, FileLinks, Platform};
+
+mod names {
+    pub(crate) const CACHE_FILE: &str = "file_links_cache.json";
+    pub(crate) const CLOSED: &str = "closed";
+}
+
+const MILLIS_PER_DAY: i64 = 24 * 3600 * 1000;
+
+#[derive(Serialize, Deserialize, Clone)]
+struct CachedFile {
+    link: DnaNexusLink,
+    size: u64,
+    state: String,
+    modified: i64,
+    #[serde(default)]
+    cached: i64,
+}
+
+struct LinkCache {
+    file: PathBuf,
+    entries: BTreeMap<String, CachedFile>,
+}
+
+fn folder_key(project: &str, folder: &str) -> String {
+    let folder = folder.split_once(':').map(|(_, folder)| folder).unwrap_or(folder);
+    format!("{}:{}/", project, folder.trim_end_matches('/'))
+}
+
+impl LinkCache {
+    fn file(conf: &Conf) -> Result<PathBuf, Error> {
+        Ok(PathBuf::from(conf.workspace.work_dir_fixed()?).join(names::CACHE_FILE))
+    }
+    fn load(conf: &Conf) -> Result<LinkCache, Error> {
+        let file = LinkCache::file(conf)?;
+        let entries =
+            if file.exists() {
+                serde_json::from_str(&fs::read_to_string(&file)?)?
+            } else {
+                BTreeMap::new()
+            };
+        Ok(LinkCache { file, entries })
+    }
+    fn save(&self) -> Result<(), Error> {
+        if let Some(parent) = self.file.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.file, serde_json::to_string_pretty(&self.entries)?)?;
+        Ok(())
+    }
+    fn get_fresh_closed(&self, folder_key: &str, name: &str, cached_after: i64)
+                        -> Option<&CachedFile> {
+        self.entries.get(&format!("{}{}", folder_key, name)).filter(|cached_file| {
+            cached_file.state == names::CLOSED && cached_file.cached > cached_after
+        })
+    }
+    fn replace_folder(&mut self, folder_key: &str, files: Vec<FileInfo>, now: i64) -> usize {
+        let mut old_entries: BTreeMap<String, CachedFile> = BTreeMap::new();
+        self.entries.retain(|key, cached_file| {
+            match key.strip_prefix(folder_key) {
+                Some(name) if !name.contains('/') => {
+                    old_entries.insert(key.clone(), cached_file.clone());
+                    false
+                }
+                _ => { true }
+            }
+        });
+        let mut n_changed: usize = 0;
+        for FileInfo { name, id, project, size, state, modified } in files {
+            let key = format!("{}{}", folder_key, name);
+            if let Some(old_entry) = old_entries.get(&key) {
+                if old_entry.modified != modified || old_entry.link.id != id {
+                    n_changed += 1
+                }
+            }
+            let link = DnaNexusLink { id, project };
+            let cached_file = CachedFile { link, size, state, modified, cached: now };
+            self.entries.insert(key, cached_file);
+        }
+        n_changed
+    }
+}
+
+fn remove_ambiguous(files: &mut Vec<FileInfo>, folder: &str, names: &[String])
+                    -> Result<(), Error> {
+    let mut seen: HashSet<String> = HashSet::new();
+    let ambiguous: HashSet<String> =
+        files.iter().filter(|file| !seen.insert(file.name.clone()))
+            .map(|file| file.name.clone()).collect();
+    let mut ambiguous_requested: Vec<&String> =
+        names.iter().filter(|name| ambiguous.contains(*name)).collect();
+    if !ambiguous_requested.is_empty() {
+        ambiguous_requested.sort();
+        let descriptions: Vec<String> =
+            ambiguous_requested.iter().map(|name| {
+                let ids: Vec<&str> =
+                    files.iter().filter(|file| file.name == **name)
+                        .map(|file| file.id.as_str()).collect();
+                format!("{} ({})", name, ids.join(", "))
+            }).collect();
+        Err(Error::from(format!("Found more than one file with the same name in '{}': {}. \
+            Remove all but one of each.", folder, descriptions.join("; "))))?
+    }
+    files.retain(|file| !ambiguous.contains(&file.name));
+    Ok(())
+}
+
+pub(crate) fn resolve_links(conf: &Conf, platform: &dyn Platform, folder: &str,
+                            names: &[String]) -> Result<FileLinks, Error> {
+    let folder_key = folder_key(&conf.project(platform)?, folder);
+    let mut cache = LinkCache::load(conf)?;
+    let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)?.as_millis() as i64;
+    let cached_after = now - conf.cache.max_age_days as i64 * MILLIS_PER_DAY;
+    let mut links_by_name: HashMap<String, DnaNexusLink> = HashMap::new();
+    for name in names {
+        match cache.get_fresh_closed(&folder_key, name, cached_after) {
+            None => { break; }
+            Some(cached_file) => {
+                links_by_name.insert(name.clone(), cached_file.link.clone());
+            }
+        }
+    }
+    if links_by_name.len() < names.len() {
+        links_by_name.clear();
+        let mut files = platform.find_files(folder)?;
+        remove_ambiguous(&mut files, folder, names)?;
+        for file in &files {
+            let link = DnaNexusLink { id: file.id.clone(), project: file.project.clone() };
+            links_by_name.insert(file.name.clone(), link);
+        }
+        let n_changed = cache.replace_folder(&folder_key, files, now);
+        if n_changed > 0 {
+            println!("Updated {} cached file links in {} that were modified since cached.",
+                     n_changed, folder)
+        }
+        cache.save()?;
+    }
+    Ok(FileLinks::new(folder, links_by_name))
+}
+
+pub(crate) fn show_cache(conf: &Conf) -> Result<(), Error> {
+    let cache = LinkCache::load(conf)?;
+    for (path, cached_file) in &cache.entries {
+        println!("{}\t{}\t{}\t{}\t{}\t{}", path, cached_file.link.id, cached_file.size,
+                 cached_file.state, cached_file.modified, cached_file.cached);
+    }
+    println!("{} cached file links in {}.", cache.entries.len(), cache.file.to_string_lossy());
+    Ok(())
+}
+
+pub(crate) fn clear_cache(conf: &Conf) -> Result<(), Error> {
+    let file = LinkCache::file(conf)?;
+    if file.exists() {
+        fs::remove_file(&file)?;
+        println!("Removed {}.", file.to_string_lossy());
+    } else {
+        println!("No cache at {}.", file.to_string_lossy());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::path::PathBuf;
+    use crate::platform::FileInfo;
+    use super::{folder_key, remove_ambiguous, LinkCache};
+
+    fn file_info(name: &str, id: &str, state: &str, modified: i64) -> FileInfo {
+        FileInfo {
+            name: name.to_string(), id: id.to_string(), project: "project-1".to_string(),
+            size: 1, state: state.to_string(), modified,
+        }
+    }
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn cache_hits_are_closed_files_cached_recently() {
+        let mut cache = LinkCache { file: PathBuf::new(), entries: BTreeMap::new() };
+        let folder = folder_key("project-1", "/vcfs/");
+        let files =
+            vec![file_info("a.vcf.gz", "file-a", "closed", 1),
+                 file_info("b.vcf.gz", "file-b", "open", 1)];
+        cache.replace_folder(&folder, files, 1000);
+        assert!(cache.get_fresh_closed(&folder, "a.vcf.gz", 999).is_some());
+        assert!(cache.get_fresh_closed(&folder, "a.vcf.gz", 1000).is_none());
+        assert!(cache.get_fresh_closed(&folder, "b.vcf.gz", 999).is_none());
+        assert!(cache.get_fresh_closed(&folder, "c.vcf.gz", 999).is_none());
+    }
+
+    #[test]
+    fn relisting_counts_modified_files_and_keeps_other_folders() {
+        let mut cache = LinkCache { file: PathBuf::new(), entries: BTreeMap::new() };
+        let vcfs = folder_key("project-1", "/vcfs");
+        let beds = folder_key("project-1", "/vcfs/beds");
+        cache.replace_folder(&beds, vec![file_info("a.bed", "file-c", "closed", 1)], 1000);
+        let files =
+            vec![file_info("a.vcf.gz", "file-a", "closed", 1),
+                 file_info("b.vcf.gz", "file-b", "closed", 1)];
+        assert_eq!(cache.replace_folder(&vcfs, files, 1000), 0);
+        let files =
+            vec![file_info("a.vcf.gz", "file-a", "closed", 1),
+                 file_info("b.vcf.gz", "file-b2", "closed", 2)];
+        assert_eq!(cache.replace_folder(&vcfs, files, 2000), 1);
+        assert_eq!(cache.get_fresh_closed(&vcfs, "b.vcf.gz", 0).unwrap().link.id, "file-b2");
+        assert!(cache.get_fresh_closed(&beds, "a.bed", 0).is_some());
+    }
+
+    #[test]
+    fn ambiguous_names_are_errors_only_when_requested() {
+        let listing = || {
+            vec![file_info("a.vcf.gz", "file-a1", "closed", 1),
+                 file_info("a.vcf.gz", "file-a2", "closed", 1),
+                 file_info("b.vcf.gz", "file-b", "closed", 1)]
+        };
+        let mut files = listing();
+        remove_ambiguous(&mut files, "/vcfs/", &names(&["b.vcf.gz"])).unwrap();
+        let remaining: Vec<&str> = files.iter().map(|file| file.name.as_str()).collect();
+        assert_eq!(remaining, vec!["b.vcf.gz"]);
+        let error =
+            remove_ambiguous(&mut listing(), "/vcfs/", &names(&["a.vcf.gz", "b.vcf.gz"]))
+                .err().unwrap();
+        assert!(error.to_string().contains("a.vcf.gz (file-a1, file-a2)"), "{}", error);
+    }
+}
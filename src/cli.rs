@@ -1,6 +1,9 @@
+use std::ffi::OsString;
+use std::iter;
 use clap::{Arg, ArgMatches, command, Command};
 use udix::error::Error;
-use udix::selection::{Choice, Config, Params, RunChoice, Selection, DataChoice, AppChoice, DataSet};
+use udix::selection::{Choice, Config, Params, RunChoice, Selection, DataChoice, AppChoice, DataSet,
+                      CacheChoice, Format, MonitorChoice, TerminateChoice};
 
 mod top_cmd {
     pub(crate) const VCFS: &str = "vcfs";
@@ -8,7 +11,10 @@ mod top_cmd {
     pub(crate) const VCFS2BED: &str = "vcfs2bed";
     pub(crate) const BED_MERGE: &str = "bed_merge";
     pub(crate) const CONFIG: &str = "config";
-    pub(crate) const CMDS: [&str; 5] = [VCFS, BEDS, VCFS2BED, BED_MERGE, CONFIG];
+    pub(crate) const CACHE: &str = "cache";
+    pub(crate) const PIPELINE: &str = "pipeline";
+    pub(crate) const APP: &str = "app";
+    pub(crate) const CMDS: [&str; 7] = [VCFS, BEDS, VCFS2BED, BED_MERGE, PIPELINE, CONFIG, CACHE];
 }
 
 mod data_sub_cmd {
@@ -20,12 +26,34 @@ mod data_sub_cmd {
 mod app_sub_cmd {
     pub(crate) const RUN: &str = "run";
     pub(crate) const MONITOR: &str = "monitor";
-    pub(crate) const CMDS: [&str; 2] = [RUN, MONITOR];
+    pub(crate) const LOGS: &str = "logs";
+    pub(crate) const FAILURES: &str = "failures";
+    pub(crate) const TERMINATE: &str = "terminate";
+    pub(crate) const RECONCILE: &str = "reconcile";
+    pub(crate) const COSTS: &str = "costs";
+    pub(crate) const VERIFY: &str = "verify";
+    pub(crate) const CMDS: [&str; 8] =
+        [RUN, MONITOR, LOGS, FAILURES, TERMINATE, RECONCILE, COSTS, VERIFY];
 }
 
 mod config_sub_cmd {
     pub(crate) const DOWNLOAD: &str = "download";
-    pub(crate) const CMDS: [&str; 1] = [DOWNLOAD];
+    pub(crate) const SHOW: &str = "show";
+    pub(crate) const VALIDATE: &str = "validate";
+    pub(crate) const UPLOAD: &str = "upload";
+    pub(crate) const DIFF: &str = "diff";
+    pub(crate) const CMDS: [&str; 5] = [DOWNLOAD, SHOW, VALIDATE, UPLOAD, DIFF];
+}
+
+mod pipeline_sub_cmd {
+    pub(crate) const RUN: &str = "run";
+    pub(crate) const CMDS: [&str; 1] = [RUN];
+}
+
+mod cache_sub_cmd {
+    pub(crate) const SHOW: &str = "show";
+    pub(crate) const CLEAR: &str = "clear";
+    pub(crate) const CMDS: [&str; 2] = [SHOW, CLEAR];
 }
 
 mod params {
@@ -33,14 +61,50 @@ mod params {
     pub(crate) const NUM: &str = "num";
     pub(crate) const DRY: &str = "dry";
     pub(crate) const PAT: &str = "pat";
+    pub(crate) const PLATFORM: &str = "platform";
+    pub(crate) const PROFILE: &str = "profile";
+    pub(crate) const ORIGIN: &str = "origin";
+    pub(crate) const FILE: &str = "file";
+    pub(crate) const WATCH: &str = "watch";
+    pub(crate) const INTERVAL: &str = "interval";
+    pub(crate) const JOB_NAME: &str = "job-name";
+    pub(crate) const STATE: &str = "state";
+    pub(crate) const YES: &str = "yes";
+    pub(crate) const FORMAT: &str = "format";
+    pub(crate) const NAMES: &str = "names";
 }
 
 mod defaults {
-    pub(crate) const CONF_FILE: &str = "/udix/conf.toml";
+    pub(crate) const PLATFORM: &str = "dx";
 }
 
 fn new_command(name: &'static str) -> Command {
-    Command::new(name).arg(Arg::new(params::CONF_FILE).long(params::CONF_FILE))
+    Command::new(name)
+        .arg(Arg::new(params::CONF_FILE).long(params::CONF_FILE)
+            .help("Config file layered over the project and user config"))
+        .arg(Arg::new(params::PLATFORM).long(params::PLATFORM)
+            .help("Platform backend: dx (default), api, fake:<dir>, record:<cassette> or \
+                replay:<cassette>"))
+}
+
+fn with_global_args(command: Command) -> Command {
+    command
+        .arg(Arg::new(params::PROFILE).long(params::PROFILE).global(true)
+            .help("Named profile from the [profiles.<name>] config sections"))
+        .arg(Arg::new(params::FORMAT).long(params::FORMAT).global(true)
+            .help("Output format of listings, surveys and job reports: text (default), json, \
+                tsv or csv"))
+}
+
+fn global_args(matches: &ArgMatches) -> Vec<OsString> {
+    let mut args: Vec<OsString> = Vec::new();
+    for param in [params::PROFILE, params::FORMAT] {
+        if let Some(value) = matches.get_one::<String>(param) {
+            args.push(OsString::from(format!("--{}", param)));
+            args.push(OsString::from(value));
+        }
+    }
+    args
 }
 
 fn new_data_command(name: &'static str) -> Command {
@@ -61,18 +125,53 @@ fn new_run_command() -> Command {
         .arg(Arg::new(params::DRY).short('d').long(params::DRY)
             .num_args(0).action(clap::ArgAction::SetTrue))
         .arg(Arg::new(params::PAT).short('p').long(params::PAT))
+        .arg(Arg::new(params::NAMES).long(params::NAMES)
+            .help("File with one job name per line to rerun, even if done, e.g. from verify"))
+}
+
+fn new_monitor_command() -> Command {
+    new_command(app_sub_cmd::MONITOR)
+        .arg(Arg::new(params::WATCH).short('w').long(params::WATCH)
+            .num_args(0).action(clap::ArgAction::SetTrue)
+            .help("Keep polling until no job is active, then exit 0 only if all jobs are done"))
+        .arg(Arg::new(params::INTERVAL).short('i').long(params::INTERVAL)
+            .help("Seconds between polls in watch mode"))
+}
+
+fn new_logs_command() -> Command {
+    new_command(app_sub_cmd::LOGS)
+        .arg(Arg::new(params::JOB_NAME).required(true)
+            .help("Name of the job, whose most recent attempt is shown"))
+}
+
+fn new_terminate_command() -> Command {
+    new_command(app_sub_cmd::TERMINATE)
+        .arg(Arg::new(params::PAT).short('p').long(params::PAT))
+        .arg(Arg::new(params::STATE).short('s').long(params::STATE).value_delimiter(',')
+            .help("Comma-separated job states to terminate, by default all active states"))
+        .arg(Arg::new(params::DRY).short('d').long(params::DRY)
+            .num_args(0).action(clap::ArgAction::SetTrue))
+        .arg(Arg::new(params::YES).short('y').long(params::YES)
+            .num_args(0).action(clap::ArgAction::SetTrue)
+            .help("Do not ask for confirmation"))
+}
+
+fn new_reconcile_command() -> Command {
+    new_command(app_sub_cmd::RECONCILE)
+        .arg(Arg::new(params::DRY).short('d').long(params::DRY)
+            .num_args(0).action(clap::ArgAction::SetTrue))
 }
 
 fn get_params_and_data_choice(top_matches: &ArgMatches) -> Result<(DataChoice, Params), Error> {
     match top_matches.subcommand() {
         Some((data_sub_cmd::LIST, sub_matches)) => {
             let data_choice = DataChoice::List;
-            let params = get_params(sub_matches);
+            let params = get_params(sub_matches)?;
             Ok((data_choice, params))
         }
         Some((data_sub_cmd::SURVEY, sub_matches)) => {
             let data_choice = DataChoice::Survey;
-            let params = get_params(sub_matches);
+            let params = get_params(sub_matches)?;
             Ok((data_choice, params))
         }
         Some((unknown_cmd, _)) => {
@@ -82,11 +181,17 @@ fn get_params_and_data_choice(top_matches: &ArgMatches) -> Result<(DataChoice, P
     }
 }
 
-fn get_params(matches: &ArgMatches) -> Params {
-    let conf_file =
-        matches.get_one::<String>(params::CONF_FILE).cloned()
-            .unwrap_or(defaults::CONF_FILE.to_string());
-    Params { conf_file }
+fn get_params(matches: &ArgMatches) -> Result<Params, Error> {
+    let conf_file = matches.get_one::<String>(params::CONF_FILE).cloned();
+    let platform =
+        matches.get_one::<String>(params::PLATFORM).cloned()
+            .unwrap_or(defaults::PLATFORM.to_string());
+    let profile = matches.get_one::<String>(params::PROFILE).cloned();
+    let format =
+        matches.get_one::<String>(params::FORMAT)
+            .map(|format| Format::try_from(format.as_str())).transpose()?
+            .unwrap_or(Format::Text);
+    Ok(Params { conf_file, platform, profile, format })
 }
 
 fn get_run_choice(matches: &ArgMatches) -> Result<RunChoice, Error> {
@@ -95,7 +200,31 @@ fn get_run_choice(matches: &ArgMatches) -> Result<RunChoice, Error> {
             .map(|s| s.parse::<usize>()).transpose()?;
     let dry = matches.get_flag(params::DRY);
     let pat = matches.get_one::<String>(params::PAT).cloned();
-    Ok(RunChoice { num, dry, pat })
+    let names = matches.get_one::<String>(params::NAMES).cloned();
+    Ok(RunChoice { num, dry, pat, names })
+}
+
+fn get_monitor_choice(matches: &ArgMatches) -> Result<MonitorChoice, Error> {
+    let watch = matches.get_flag(params::WATCH);
+    let interval =
+        matches.get_one::<String>(params::INTERVAL)
+            .map(|s| s.parse::<u64>()).transpose()?;
+    Ok(MonitorChoice { watch, interval })
+}
+
+fn get_terminate_choice(matches: &ArgMatches) -> TerminateChoice {
+    let pat = matches.get_one::<String>(params::PAT).cloned();
+    let states =
+        matches.get_many::<String>(params::STATE)
+            .map(|states| states.cloned().collect()).unwrap_or_default();
+    let dry = matches.get_flag(params::DRY);
+    let yes = matches.get_flag(params::YES);
+    TerminateChoice { pat, states, dry, yes }
+}
+
+fn get_job_name(matches: &ArgMatches) -> Result<String, Error> {
+    matches.get_one::<String>(params::JOB_NAME).cloned()
+        .ok_or_else(|| Error::from("Missing job name."))
 }
 
 fn known_cmds_are(cmds: &[&str]) -> String {
@@ -114,31 +243,93 @@ fn missing_cmd_error(cmds: &[&str]) -> Error {
     Error::from(format!("Missing command. {}", known_cmds_are(cmds)))
 }
 
+fn new_app_command(name: &'static str) -> Command {
+    Command::new(name)
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommand(new_run_command())
+        .subcommand(new_monitor_command())
+        .subcommand(new_logs_command())
+        .subcommand(new_command(app_sub_cmd::FAILURES))
+        .subcommand(new_terminate_command())
+        .subcommand(new_reconcile_command())
+        .subcommand(new_command(app_sub_cmd::COSTS))
+        .subcommand(new_command(app_sub_cmd::VERIFY))
+}
+
+fn get_app_selection(app: &str, app_matches: &ArgMatches) -> Result<Selection, Error> {
+    let (app_choice, matches) =
+        match app_matches.subcommand() {
+            Some((app_sub_cmd::RUN, matches)) => {
+                (AppChoice::Run(get_run_choice(matches)?), matches)
+            }
+            Some((app_sub_cmd::MONITOR, matches)) => {
+                (AppChoice::Monitor(get_monitor_choice(matches)?), matches)
+            }
+            Some((app_sub_cmd::LOGS, matches)) => {
+                (AppChoice::Logs { job_name: get_job_name(matches)? }, matches)
+            }
+            Some((app_sub_cmd::FAILURES, matches)) => { (AppChoice::Failures, matches) }
+            Some((app_sub_cmd::TERMINATE, matches)) => {
+                (AppChoice::Terminate(get_terminate_choice(matches)), matches)
+            }
+            Some((app_sub_cmd::RECONCILE, matches)) => {
+                (AppChoice::Reconcile { dry: matches.get_flag(params::DRY) }, matches)
+            }
+            Some((app_sub_cmd::COSTS, matches)) => { (AppChoice::Costs, matches) }
+            Some((app_sub_cmd::VERIFY, matches)) => { (AppChoice::Verify, matches) }
+            Some((unknown_cmd, _)) => {
+                Err(unknown_cmd_error(unknown_cmd, &app_sub_cmd::CMDS))?
+            }
+            None => {
+                Err(missing_cmd_error(&app_sub_cmd::CMDS))?
+            }
+        };
+    let choice = Choice::App { app: app.to_string(), app_choice };
+    let params = get_params(matches)?;
+    Ok(Selection { choice, params })
+}
+
 pub(crate) fn get_selection() -> Result<Selection, Error> {
-    let matches = command!()
+    let matches = with_global_args(command!())
         .subcommand_required(true)
         .arg_required_else_help(true)
+        .allow_external_subcommands(true)
         .subcommand(new_data_command(top_cmd::VCFS))
         .subcommand(new_data_command(top_cmd::BEDS))
+        .subcommand(new_app_command(top_cmd::VCFS2BED))
+        .subcommand(new_app_command(top_cmd::BED_MERGE))
         .subcommand(
-            Command::new(top_cmd::VCFS2BED)
+            Command::new(top_cmd::PIPELINE)
                 .subcommand_required(true)
                 .arg_required_else_help(true)
                 .subcommand(new_run_command())
-                .subcommand(new_command(app_sub_cmd::MONITOR))
         ).subcommand(
-        Command::new(top_cmd::BED_MERGE)
-            .subcommand_required(true)
-            .arg_required_else_help(true)
-            .subcommand(new_run_command())
-            .subcommand(new_command(app_sub_cmd::MONITOR))
-    ).subcommand(
         Command::new(top_cmd::CONFIG)
             .subcommand_required(true)
             .arg_required_else_help(true)
             .subcommand(
                 new_command(config_sub_cmd::DOWNLOAD)
             )
+            .subcommand(
+                new_command(config_sub_cmd::SHOW)
+                    .arg(Arg::new(params::ORIGIN).long(params::ORIGIN)
+                        .num_args(0).action(clap::ArgAction::SetTrue)
+                        .help("Show which layer each value came from"))
+            )
+            .subcommand(new_command(config_sub_cmd::VALIDATE))
+            .subcommand(
+                new_command(config_sub_cmd::UPLOAD)
+                    .arg(Arg::new(params::FILE).short('f').long(params::FILE)
+                        .help("File to upload, by default the local copy of the project config"))
+            )
+            .subcommand(new_command(config_sub_cmd::DIFF))
+    ).subcommand(
+        Command::new(top_cmd::CACHE)
+            .subcommand_required(true)
+            .arg_required_else_help(true)
+            .subcommand(new_command(cache_sub_cmd::SHOW))
+            .subcommand(new_command(cache_sub_cmd::CLEAR))
     ).get_matches();
     match matches.subcommand() {
         Some((top_cmd::VCFS, vcfs_matches)) => {
@@ -155,65 +346,91 @@ pub(crate) fn get_selection() -> Result<Selection, Error> {
             let choice = Choice::Data { data_set, data_choice };
             Ok(Selection { choice, params })
         }
-        Some((top_cmd::VCFS2BED, vcfs2bed_matches)) => {
-            match vcfs2bed_matches.subcommand() {
-                Some((app_sub_cmd::RUN, matches)) => {
+        Some((app @ (top_cmd::VCFS2BED | top_cmd::BED_MERGE), app_matches)) => {
+            get_app_selection(app, app_matches)
+        }
+        Some((top_cmd::PIPELINE, pipeline_matches)) => {
+            match pipeline_matches.subcommand() {
+                Some((pipeline_sub_cmd::RUN, matches)) => {
                     let run = get_run_choice(matches)?;
-                    let choice = Choice::Vcfs2Bed(AppChoice::Run(run));
-                    let params = get_params(matches);
-                    Ok(Selection { choice, params })
-                }
-                Some((app_sub_cmd::MONITOR, matches)) => {
-                    let choice = Choice::Vcfs2Bed(AppChoice::Monitor);
-                    let params = get_params(matches);
+                    let choice = Choice::Pipeline(run);
+                    let params = get_params(matches)?;
                     Ok(Selection { choice, params })
                 }
                 Some((unknown_cmd, _)) => {
-                    Err(unknown_cmd_error(unknown_cmd, &app_sub_cmd::CMDS))
+                    Err(unknown_cmd_error(unknown_cmd, &pipeline_sub_cmd::CMDS))
                 }
                 None => {
-                    Err(missing_cmd_error(&app_sub_cmd::CMDS))
+                    Err(missing_cmd_error(&pipeline_sub_cmd::CMDS))
                 }
             }
         }
-        Some((top_cmd::BED_MERGE, bed_merge_matches)) => {
-            match bed_merge_matches.subcommand() {
-                Some((app_sub_cmd::RUN, matches)) => {
-                    let run = get_run_choice(matches)?;
-                    let choice = Choice::BedMerge(AppChoice::Run(run));
-                    let params = get_params(matches);
+        Some((top_cmd::CONFIG, config_matches)) => {
+            match config_matches.subcommand() {
+                Some((config_sub_cmd::DOWNLOAD, matches)) => {
+                    let choice = Choice::Config(Config::Download);
+                    let params = get_params(matches)?;
+                    Ok(Selection { choice, params })
+                }
+                Some((config_sub_cmd::SHOW, matches)) => {
+                    let origin = matches.get_flag(params::ORIGIN);
+                    let choice = Choice::Config(Config::Show { origin });
+                    let params = get_params(matches)?;
                     Ok(Selection { choice, params })
                 }
-                Some((app_sub_cmd::MONITOR, matches)) => {
-                    let choice = Choice::BedMerge(AppChoice::Monitor);
-                    let params = get_params(matches);
+                Some((config_sub_cmd::VALIDATE, matches)) => {
+                    let choice = Choice::Config(Config::Validate);
+                    let params = get_params(matches)?;
+                    Ok(Selection { choice, params })
+                }
+                Some((config_sub_cmd::UPLOAD, matches)) => {
+                    let file = matches.get_one::<String>(params::FILE).cloned();
+                    let choice = Choice::Config(Config::Upload { file });
+                    let params = get_params(matches)?;
+                    Ok(Selection { choice, params })
+                }
+                Some((config_sub_cmd::DIFF, matches)) => {
+                    let choice = Choice::Config(Config::Diff);
+                    let params = get_params(matches)?;
                     Ok(Selection { choice, params })
                 }
                 Some((unknown_cmd, _)) => {
-                    Err(unknown_cmd_error(unknown_cmd, &app_sub_cmd::CMDS))
+                    Err(unknown_cmd_error(unknown_cmd, &config_sub_cmd::CMDS))
                 }
                 None => {
-                    Err(missing_cmd_error(&app_sub_cmd::CMDS))
+                    Err(missing_cmd_error(&config_sub_cmd::CMDS))
                 }
             }
         }
-        Some((top_cmd::CONFIG, config_matches)) => {
-            match config_matches.subcommand() {
-                Some((config_sub_cmd::DOWNLOAD, matches)) => {
-                    let choice = Choice::Config(Config::Download);
-                    let params = get_params(matches);
+        Some((top_cmd::CACHE, cache_matches)) => {
+            match cache_matches.subcommand() {
+                Some((cache_sub_cmd::SHOW, matches)) => {
+                    let choice = Choice::Cache(CacheChoice::Show);
+                    let params = get_params(matches)?;
+                    Ok(Selection { choice, params })
+                }
+                Some((cache_sub_cmd::CLEAR, matches)) => {
+                    let choice = Choice::Cache(CacheChoice::Clear);
+                    let params = get_params(matches)?;
                     Ok(Selection { choice, params })
                 }
                 Some((unknown_cmd, _)) => {
-                    Err(unknown_cmd_error(unknown_cmd, &config_sub_cmd::CMDS))
+                    Err(unknown_cmd_error(unknown_cmd, &cache_sub_cmd::CMDS))
                 }
                 None => {
-                    Err(missing_cmd_error(&config_sub_cmd::CMDS))
+                    Err(missing_cmd_error(&cache_sub_cmd::CMDS))
                 }
             }
         }
-        Some((unknown_cmd, _)) => {
-            Err(unknown_cmd_error(unknown_cmd, &top_cmd::CMDS))
+        Some((app, external_matches)) => {
+            let args =
+                external_matches.get_many::<OsString>("").into_iter().flatten().cloned();
+            let app_matches =
+                with_global_args(new_app_command(top_cmd::APP))
+                    .bin_name(format!("udix {}", app))
+                    .get_matches_from(iter::once(OsString::from(app))
+                        .chain(global_args(&matches)).chain(args));
+            get_app_selection(app, &app_matches)
         }
         None => {
             Err(missing_cmd_error(&top_cmd::CMDS))
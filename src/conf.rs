@@ -1,16 +1,30 @@
+use std::collections::BTreeMap;
 use std::fs;
-use std::fs::read_to_string;
 use std::path::{Path, PathBuf};
 use std::time::{Duration, SystemTime};
-use crate::{dx, env};
-use crate::error::Error;
-use serde::Deserialize;
+use crate::env;
+use crate::conf::layers::{flatten_values, Layer, LayeredConf, read_table_file};
+use crate::error::{DxErrorKind, Error, ErrorKind};
+use serde::{Deserialize, Deserializer};
+use toml::{Table, Value};
+use crate::platform::Platform;
+use crate::selection::Params;
+
+mod layers;
 
 #[derive(Deserialize)]
 pub(crate) struct Conf {
     pub(crate) data: DataConf,
     pub(crate) workspace: WorkspaceConf,
-    pub(crate) misc: Misc
+    pub(crate) misc: Misc,
+    #[serde(default)]
+    pub(crate) specs: Table,
+    #[serde(default)]
+    pub(crate) jobs: JobsConf,
+    #[serde(default)]
+    pub(crate) monitor: MonitorConf,
+    #[serde(default)]
+    pub(crate) cache: CacheConf,
 }
 
 #[derive(Deserialize)]
@@ -22,13 +36,77 @@ pub(crate) struct DataConf {
 #[derive(Deserialize)]
 pub(crate) struct WorkspaceConf {
     pub(crate) work_dir: String,
+    pub(crate) project: Option<String>,
+    pub(crate) launched_by: Option<String>,
 }
 
 #[derive(Deserialize)]
 pub(crate) struct Misc {
+    #[serde(deserialize_with = "string_or_integer")]
     pub(crate) start_date: String
 }
 
+fn string_or_integer<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StringOrInteger {
+        String(String),
+        Integer(i64),
+    }
+    match StringOrInteger::deserialize(deserializer)? {
+        StringOrInteger::String(string) => { Ok(string) }
+        StringOrInteger::Integer(integer) => { Ok(integer.to_string()) }
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct JobsConf {
+    #[serde(default = "default_resubmit_states")]
+    pub(crate) resubmit_states: Vec<String>,
+    #[serde(default = "default_max_attempts")]
+    pub(crate) max_attempts: usize,
+    #[serde(default = "default_confirm_terminate_above")]
+    pub(crate) confirm_terminate_above: usize,
+}
+
+fn default_resubmit_states() -> Vec<String> {
+    vec!["failed".to_string(), "restartable".to_string()]
+}
+
+fn default_max_attempts() -> usize { 3 }
+
+fn default_confirm_terminate_above() -> usize { 10 }
+
+impl Default for JobsConf {
+    fn default() -> Self {
+        JobsConf {
+            resubmit_states: default_resubmit_states(),
+            max_attempts: default_max_attempts(),
+            confirm_terminate_above: default_confirm_terminate_above(),
+        }
+    }
+}
+
+#[derive(Deserialize, Default)]
+pub(crate) struct MonitorConf {
+    pub(crate) on_finish: Option<String>,
+    pub(crate) finish_marker: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct CacheConf {
+    #[serde(default = "default_max_age_days")]
+    pub(crate) max_age_days: u64,
+}
+
+fn default_max_age_days() -> u64 { 7 }
+
+impl Default for CacheConf {
+    fn default() -> Self {
+        CacheConf { max_age_days: default_max_age_days() }
+    }
+}
+
 fn fix_home_dir(file: &str) -> Result<String, Error> {
     if let Some(file_in_home) = file.strip_prefix("~/") {
         Ok(format!("{}/{}", env::get_home()?, file_in_home))
@@ -43,12 +121,51 @@ impl WorkspaceConf {
     }
 }
 
-fn get_local_conf_file() -> Result<PathBuf, Error> {
-    Ok(PathBuf::from(format!("{}/.config/udix/udix.toml", env::get_home()?)))
+impl MonitorConf {
+    pub(crate) fn finish_marker_fixed(&self) -> Result<Option<String>, Error> {
+        self.finish_marker.as_deref().map(fix_home_dir).transpose()
+    }
+}
+
+impl Conf {
+    pub(crate) fn project(&self, platform: &dyn Platform) -> Result<String, Error> {
+        match &self.workspace.project {
+            None => { platform.get_project() }
+            Some(project) => { Ok(project.clone()) }
+        }
+    }
+    pub(crate) fn in_project(&self, path: &str) -> String {
+        match &self.workspace.project {
+            Some(project) if !path.contains(':') => { format!("{}:{}", project, path) }
+            _ => { path.to_string() }
+        }
+    }
+}
+
+fn get_local_conf_file(platform: &dyn Platform, profile: Option<&str>)
+                       -> Result<PathBuf, Error> {
+    let project = platform.get_project()?;
+    let name =
+        match profile {
+            None => { REMOTE_CONF_NAME.to_string() }
+            Some(profile) => { format!("udix.{}.toml", profile) }
+        };
+    Ok(PathBuf::from(format!("{}/.config/udix/remote/{}/{}", env::get_home()?, project, name)))
+}
+
+fn get_user_conf_file() -> Result<PathBuf, Error> {
+    Ok(PathBuf::from(format!("{}/.config/udix/local.toml", env::get_home()?)))
 }
 
+const REMOTE_CONF_DIR: &str = "/udix/";
+const REMOTE_CONF_NAME: &str = "udix.toml";
 const REMOTE_CONF_FILE: &str = "/udix/udix.toml";
 
+const DEFAULTS: &str = r#"
+[workspace]
+work_dir = "~/udix/work"
+"#;
+
 fn fresh_conf_file_exists(file: &Path) -> Result<bool, Error> {
     if Path::new(file).exists() {
         let duration = Duration::from_secs(3600);
@@ -59,24 +176,145 @@ fn fresh_conf_file_exists(file: &Path) -> Result<bool, Error> {
     }
 }
 
-fn download_conf_file(file: &Path) -> Result<(), Error> {
-    let file_str =
-        file.to_str().ok_or_else(|| {
-            Error::from(format!("Cannot convert '{}' to string", file.to_string_lossy()))
-        })?;
+fn download_conf_file(platform: &dyn Platform, file: &Path) -> Result<(), Error> {
     if let Some(parent) = file.parent() {
         fs::create_dir_all(parent)?;
     }
-    dx::run(&["download", REMOTE_CONF_FILE, "--overwrite", "--output", file_str])?;
-    Ok(())
+    platform.download(REMOTE_CONF_FILE, file)
 }
 
-pub(crate) fn read_conf() -> Result<Conf, Error> {
-    let conf_file = get_local_conf_file()?;
+fn read_remote_layer(platform: &dyn Platform, profile: Option<&str>)
+                     -> Result<Option<Table>, Error> {
+    let conf_file = get_local_conf_file(platform, profile)?;
     if !fresh_conf_file_exists(&conf_file)? {
-        download_conf_file(&conf_file)?
+        match download_conf_file(platform, &conf_file) {
+            Ok(()) => {}
+            Err(error) => {
+                match error.kind() {
+                    ErrorKind::Dx(DxErrorKind::NotFound) if !conf_file.exists() => {
+                        return Ok(None)
+                    }
+                    _ => { return Err(error) }
+                }
+            }
+        }
+    }
+    Ok(Some(read_table_file(&conf_file)?))
+}
+
+pub(crate) fn read_layered_conf(platform: &dyn Platform, params: &Params)
+                                -> Result<LayeredConf, Error> {
+    let mut layered_conf = LayeredConf::new();
+    layered_conf.add_layer(&Layer::Defaults, DEFAULTS.parse::<Table>()?);
+    if let Some(remote_table) = read_remote_layer(platform, params.profile.as_deref())? {
+        layered_conf.add_layer(&Layer::Remote(REMOTE_CONF_FILE.to_string()), remote_table);
+    }
+    let user_conf_file = get_user_conf_file()?;
+    if user_conf_file.exists() {
+        let user_layer = Layer::User(user_conf_file.to_string_lossy().to_string());
+        layered_conf.add_layer(&user_layer, read_table_file(&user_conf_file)?);
+    }
+    if let Some(conf_file) = &params.conf_file {
+        let conf_file = fix_home_dir(conf_file)?;
+        let table = read_table_file(Path::new(&conf_file))?;
+        layered_conf.add_layer(&Layer::ConfFile(conf_file), table);
     }
-    let conf_string = read_to_string(conf_file)?;
-    let conf = toml::from_str::<Conf>(&conf_string)?;
+    if let Some(profile) = &params.profile {
+        layered_conf.add_profile_layer(profile)?;
+    }
+    layered_conf.add_env_layer(env::get_all());
+    Ok(layered_conf)
+}
+
+pub(crate) fn read_conf(platform: &dyn Platform, params: &Params) -> Result<Conf, Error> {
+    let layered_conf = read_layered_conf(platform, params)?;
+    let conf = Value::Table(layered_conf.table).try_into::<Conf>()?;
     Ok(conf)
-}
\ No newline at end of file
+}
+
+pub(crate) fn show_conf(platform: &dyn Platform, params: &Params, with_origin: bool)
+                        -> Result<(), Error> {
+    read_layered_conf(platform, params)?.show(with_origin);
+    Ok(())
+}
+
+pub(crate) fn force_download_conf(platform: &dyn Platform, params: &Params)
+                                  -> Result<(), Error> {
+    let conf_file = get_local_conf_file(platform, params.profile.as_deref())?;
+    download_conf_file(platform, &conf_file)?;
+    println!("Downloaded {} to {}.", REMOTE_CONF_FILE, conf_file.to_string_lossy());
+    Ok(())
+}
+
+fn file_to_upload(platform: &dyn Platform, params: &Params, file: Option<&str>)
+                  -> Result<PathBuf, Error> {
+    match file {
+        None => { get_local_conf_file(platform, params.profile.as_deref()) }
+        Some(file) => { Ok(PathBuf::from(fix_home_dir(file)?)) }
+    }
+}
+
+pub(crate) fn upload_conf(platform: &dyn Platform, params: &Params, file: Option<&str>)
+                          -> Result<(), Error> {
+    let file = file_to_upload(platform, params, file)?;
+    let table = read_table_file(&file)?;
+    let mut layered_conf = LayeredConf::new();
+    layered_conf.add_layer(&Layer::Defaults, DEFAULTS.parse::<Table>()?);
+    layered_conf.add_layer(&Layer::Remote(file.to_string_lossy().to_string()), table);
+    Value::Table(layered_conf.table).try_into::<Conf>()?;
+    let old_ids: Vec<String> =
+        platform.find_files(REMOTE_CONF_DIR)?.into_iter()
+            .filter(|file_info| { file_info.name == REMOTE_CONF_NAME })
+            .map(|file_info| { file_info.id }).collect();
+    let new_id = platform.upload(&file, REMOTE_CONF_FILE)?;
+    let old_ids: Vec<String> = old_ids.into_iter().filter(|id| *id != new_id).collect();
+    if !old_ids.is_empty() {
+        platform.remove_files(&old_ids)?;
+    }
+    println!("Uploaded {} to {} ({}).", file.to_string_lossy(), REMOTE_CONF_FILE, new_id);
+    Ok(())
+}
+
+fn flatten_to_strings(table: &Table) -> BTreeMap<String, String> {
+    let mut values: Vec<(String, &Value)> = Vec::new();
+    flatten_values(table, "", &mut values);
+    values.into_iter().map(|(key, value)| { (key, value.to_string()) }).collect()
+}
+
+pub(crate) fn diff_conf(platform: &dyn Platform, params: &Params) -> Result<(), Error> {
+    let local_file = get_local_conf_file(platform, params.profile.as_deref())?;
+    let remote_file = local_file.with_extension("toml.remote");
+    download_conf_file(platform, &remote_file)?;
+    let remote_values = flatten_to_strings(&read_table_file(&remote_file)?);
+    fs::remove_file(&remote_file)?;
+    let local_values =
+        if local_file.exists() {
+            flatten_to_strings(&read_table_file(&local_file)?)
+        } else {
+            BTreeMap::new()
+        };
+    println!("--- {} (remote)", REMOTE_CONF_FILE);
+    println!("+++ {} (local)", local_file.to_string_lossy());
+    let mut n_differences: usize = 0;
+    let mut keys: Vec<&String> = remote_values.keys().chain(local_values.keys()).collect();
+    keys.sort();
+    keys.dedup();
+    for key in keys {
+        match (remote_values.get(key), local_values.get(key)) {
+            (Some(remote), Some(local)) if remote == local => {}
+            (remote, local) => {
+                if let Some(remote) = remote {
+                    println!("-{} = {}", key, remote)
+                }
+                if let Some(local) = local {
+                    println!("+{} = {}", key, local)
+                }
+                n_differences += 1;
+            }
+        }
+    }
+    if n_differences == 0 {
+        println!("Local and remote config are identical.")
+    }
+    Ok(())
+}
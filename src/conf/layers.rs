@@ -0,0 +1,302 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use std::fs::read_to_string;
+use toml::{Table, Value};
+use crate::error::Error;
+
+const ENV_PREFIX: &str = "UDIX_";
+const ENV_SEPARATOR: &str = "__";
+const PROFILES_KEY: &str = "profiles";
+const WORKSPACE_KEY: &str = "workspace";
+const WORK_DIR_KEY: &str = "work_dir";
+
+pub(crate) enum Layer {
+    Defaults,
+    Remote(String),
+    User(String),
+    ConfFile(String),
+    Profile(String),
+    Env(String),
+}
+
+pub(crate) struct LayeredConf {
+    pub(crate) table: Table,
+    pub(crate) origins: BTreeMap<String, String>,
+    work_dir_profile: Option<String>,
+}
+
+impl Display for Layer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Layer::Defaults => { write!(f, "built-in defaults") }
+            Layer::Remote(file) => { write!(f, "remote project file {}", file) }
+            Layer::User(file) => { write!(f, "user file {}", file) }
+            Layer::ConfFile(file) => { write!(f, "conf file {}", file) }
+            Layer::Profile(name) => { write!(f, "profile {}", name) }
+            Layer::Env(var) => { write!(f, "environment variable {}", var) }
+        }
+    }
+}
+
+fn merge(target: &mut Table, source: Table, prefix: &str, origin: &str,
+         origins: &mut BTreeMap<String, String>) {
+    for (key, value) in source {
+        let full_key = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+        match (target.get_mut(&key), value) {
+            (Some(Value::Table(target_table)), Value::Table(source_table)) => {
+                merge(target_table, source_table, &full_key, origin, origins);
+            }
+            (_, Value::Table(source_table)) => {
+                origins.remove(&full_key);
+                let mut target_table = Table::new();
+                merge(&mut target_table, source_table, &full_key, origin, origins);
+                target.insert(key, Value::Table(target_table));
+            }
+            (_, value) => {
+                let nested_prefix = format!("{}.", full_key);
+                origins.retain(|origin_key, _| { !origin_key.starts_with(&nested_prefix) });
+                origins.insert(full_key, origin.to_string());
+                target.insert(key, value);
+            }
+        }
+    }
+}
+
+pub(crate) fn flatten_values<'a>(table: &'a Table, prefix: &str,
+                                 values: &mut Vec<(String, &'a Value)>) {
+    for (key, value) in table {
+        let full_key = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+        match value {
+            Value::Table(nested) => { flatten_values(nested, &full_key, values) }
+            _ => { values.push((full_key, value)) }
+        }
+    }
+}
+
+fn profile_work_dir(work_dir: &str, profile: &str) -> String {
+    format!("{}/{}", work_dir.trim_end_matches('/'), profile)
+}
+
+fn parse_env_value(raw: &str) -> Value {
+    let parsed = format!("value = {}", raw).parse::<Table>().ok()
+        .and_then(|mut table| table.remove("value"));
+    match parsed {
+        Some(value @ (Value::Boolean(_) | Value::Integer(_) | Value::Float(_)
+        | Value::Array(_))) => { value }
+        _ => { Value::String(raw.to_string()) }
+    }
+}
+
+pub(crate) fn read_table_file(file: &Path) -> Result<Table, Error> {
+    let string = read_to_string(file).map_err(|error| {
+        Error::from(format!("Could not read {}: {}", file.to_string_lossy(), error))
+    })?;
+    Ok(string.parse::<Table>()?)
+}
+
+impl LayeredConf {
+    pub(crate) fn new() -> LayeredConf {
+        let table = Table::new();
+        let origins: BTreeMap<String, String> = BTreeMap::new();
+        LayeredConf { table, origins, work_dir_profile: None }
+    }
+    pub(crate) fn add_layer(&mut self, layer: &Layer, table: Table) {
+        merge(&mut self.table, table, "", &layer.to_string(), &mut self.origins);
+    }
+    pub(crate) fn add_profile_layer(&mut self, profile: &str) -> Result<(), Error> {
+        let profiles = self.table.get(PROFILES_KEY).and_then(|profiles| profiles.as_table());
+        let profile_table =
+            match profiles.and_then(|profiles| profiles.get(profile)) {
+                Some(Value::Table(profile_table)) => { profile_table.clone() }
+                _ => {
+                    let known: Vec<&str> =
+                        profiles.map(|profiles| {
+                            profiles.keys().map(|key| key.as_str()).collect()
+                        }).unwrap_or_default();
+                    let message =
+                        if known.is_empty() {
+                            format!("Unknown profile '{}'. No profiles are configured.", profile)
+                        } else {
+                            format!("Unknown profile '{}'. Known profiles are {}.", profile,
+                                    known.join(", "))
+                        };
+                    Err(Error::from(message))?
+                }
+            };
+        let layer = Layer::Profile(profile.to_string());
+        let profile_sets_work_dir =
+            profile_table.get(WORKSPACE_KEY).and_then(|workspace| workspace.as_table())
+                .map(|workspace| workspace.contains_key(WORK_DIR_KEY)).unwrap_or(false);
+        self.add_layer(&layer, profile_table);
+        if !profile_sets_work_dir {
+            let work_dir =
+                self.table.get(WORKSPACE_KEY).and_then(|workspace| workspace.get(WORK_DIR_KEY))
+                    .and_then(|work_dir| work_dir.as_str())
+                    .map(|work_dir| profile_work_dir(work_dir, profile));
+            if let Some(work_dir) = work_dir {
+                let mut workspace = Table::new();
+                workspace.insert(WORK_DIR_KEY.to_string(), Value::String(work_dir));
+                let mut table = Table::new();
+                table.insert(WORKSPACE_KEY.to_string(), Value::Table(workspace));
+                self.add_layer(&layer, table);
+            }
+            self.work_dir_profile = Some(profile.to_string());
+        }
+        Ok(())
+    }
+    pub(crate) fn add_env_layer(&mut self, vars: impl Iterator<Item=(String, String)>) {
+        let mut vars: Vec<(String, String)> = vars.collect();
+        vars.sort();
+        for (var, raw) in vars {
+            if let Some(path) = var.strip_prefix(ENV_PREFIX) {
+                if !path.contains(ENV_SEPARATOR) {
+                    continue;
+                }
+                let keys: Vec<String> =
+                    path.split(ENV_SEPARATOR).map(|key| key.to_lowercase()).collect();
+                let mut value = parse_env_value(&raw);
+                if let ([workspace_key, work_dir_key], Some(profile), Value::String(work_dir)) =
+                    (keys.as_slice(), &self.work_dir_profile, &value) {
+                    if workspace_key == WORKSPACE_KEY && work_dir_key == WORK_DIR_KEY {
+                        value = Value::String(profile_work_dir(work_dir, profile));
+                    }
+                }
+                for key in keys.iter().rev() {
+                    let mut table = Table::new();
+                    table.insert(key.clone(), value);
+                    value = Value::Table(table);
+                }
+                if let Value::Table(table) = value {
+                    self.add_layer(&Layer::Env(var.clone()), table)
+                }
+            }
+        }
+    }
+    pub(crate) fn values(&self) -> Vec<(String, &Value)> {
+        let mut values: Vec<(String, &Value)> = Vec::new();
+        flatten_values(&self.table, "", &mut values);
+        values
+    }
+    pub(crate) fn show(&self, with_origin: bool) {
+        for (key, value) in self.values() {
+            if with_origin {
+                let origin = self.origins.get(&key).map(|origin| origin.as_str()).unwrap_or("");
+                println!("{} = {}    # {}", key, value, origin)
+            } else {
+                println!("{} = {}", key, value)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use toml::{Table, Value};
+    use super::{Layer, LayeredConf};
+
+    fn table(toml: &str) -> Table {
+        toml.parse::<Table>().unwrap()
+    }
+
+    fn layered(layers: &[(Layer, &str)]) -> LayeredConf {
+        let mut layered_conf = LayeredConf::new();
+        for (layer, toml) in layers {
+            layered_conf.add_layer(layer, table(toml));
+        }
+        layered_conf
+    }
+
+    fn origin<'a>(layered_conf: &'a LayeredConf, key: &str) -> &'a str {
+        layered_conf.origins.get(key).map(|origin| origin.as_str()).unwrap_or("")
+    }
+
+    #[test]
+    fn later_layers_override_and_track_origin() {
+        let layered_conf = layered(&[
+            (Layer::Defaults, "[workspace]\nwork_dir = \"~/udix/work\"\n"),
+            (Layer::Remote("/udix/udix.toml".to_string()),
+             "[workspace]\nwork_dir = \"/work\"\n[misc]\nstart_date = \"2024-01-01\"\n"),
+            (Layer::User("local.toml".to_string()), "[misc]\nstart_date = \"2024-02-01\"\n"),
+        ]);
+        assert_eq!(layered_conf.table["workspace"]["work_dir"].as_str(), Some("/work"));
+        assert_eq!(layered_conf.table["misc"]["start_date"].as_str(), Some("2024-02-01"));
+        assert_eq!(origin(&layered_conf, "workspace.work_dir"),
+                   "remote project file /udix/udix.toml");
+        assert_eq!(origin(&layered_conf, "misc.start_date"), "user file local.toml");
+    }
+
+    #[test]
+    fn scalar_replacing_table_drops_nested_origins() {
+        let layered_conf = layered(&[
+            (Layer::Defaults, "[specs.vcfs2bed]\ninstance_type = \"mem1\"\n"),
+            (Layer::ConfFile("conf.toml".to_string()), "specs = 3\n"),
+        ]);
+        assert_eq!(layered_conf.table["specs"].as_integer(), Some(3));
+        assert!(!layered_conf.origins.contains_key("specs.vcfs2bed.instance_type"));
+        assert_eq!(origin(&layered_conf, "specs"), "conf file conf.toml");
+    }
+
+    #[test]
+    fn env_vars_set_nested_typed_values() {
+        let mut layered_conf = layered(&[(Layer::Defaults, "[jobs]\nmax_attempts = 3\n")]);
+        let vars = [
+            ("UDIX_JOBS__MAX_ATTEMPTS", "5"),
+            ("UDIX_JOBS__RESUBMIT_STATES", "[\"failed\", \"terminated\"]"),
+            ("UDIX_MISC__START_DATE", "2024-02-02"),
+            ("UDIX_WORKSPACE__PROJECT", "project-abc"),
+            ("UDIX_NO_SEPARATOR", "ignored"),
+            ("HOME", "/home/user"),
+        ];
+        layered_conf.add_env_layer(vars.iter().map(|(var, value)| {
+            (var.to_string(), value.to_string())
+        }));
+        assert_eq!(layered_conf.table["jobs"]["max_attempts"].as_integer(), Some(5));
+        let states = Value::Array(vec![Value::from("failed"), Value::from("terminated")]);
+        assert_eq!(layered_conf.table["jobs"]["resubmit_states"], states);
+        assert_eq!(layered_conf.table["misc"]["start_date"],
+                   Value::String("2024-02-02".to_string()));
+        assert_eq!(layered_conf.table["workspace"]["project"].as_str(), Some("project-abc"));
+        assert!(!layered_conf.table.contains_key("no_separator"));
+        assert_eq!(origin(&layered_conf, "jobs.max_attempts"),
+                   "environment variable UDIX_JOBS__MAX_ATTEMPTS");
+    }
+
+    #[test]
+    fn profile_overrides_and_moves_work_dir() {
+        let mut layered_conf = layered(&[
+            (Layer::Defaults, "[workspace]\nwork_dir = \"/work/\"\n[misc]\nstart_date = \"a\"\n\
+                [profiles.wgs.misc]\nstart_date = \"b\"\n"),
+        ]);
+        layered_conf.add_profile_layer("wgs").unwrap();
+        assert_eq!(layered_conf.table["misc"]["start_date"].as_str(), Some("b"));
+        assert_eq!(layered_conf.table["workspace"]["work_dir"].as_str(), Some("/work/wgs"));
+        assert_eq!(origin(&layered_conf, "workspace.work_dir"), "profile wgs");
+        let error = layered_conf.add_profile_layer("wes").unwrap_err();
+        assert!(error.to_string().contains("Known profiles are wgs"), "{}", error);
+    }
+
+    #[test]
+    fn profile_keeps_its_own_work_dir() {
+        let mut layered_conf = layered(&[
+            (Layer::Defaults, "[workspace]\nwork_dir = \"/work\"\n\
+                [profiles.wgs.workspace]\nwork_dir = \"/wgs\"\n"),
+        ]);
+        layered_conf.add_profile_layer("wgs").unwrap();
+        assert_eq!(layered_conf.table["workspace"]["work_dir"].as_str(), Some("/wgs"));
+    }
+
+    #[test]
+    fn env_work_dir_keeps_the_profile_suffix() {
+        let mut layered_conf = layered(&[
+            (Layer::Defaults, "[workspace]\nwork_dir = \"/work\"\n[profiles.wgs.misc]\n\
+                start_date = \"b\"\n"),
+        ]);
+        layered_conf.add_profile_layer("wgs").unwrap();
+        let vars = [("UDIX_WORKSPACE__WORK_DIR".to_string(), "/scratch/".to_string())];
+        layered_conf.add_env_layer(vars.into_iter());
+        assert_eq!(layered_conf.table["workspace"]["work_dir"].as_str(), Some("/scratch/wgs"));
+        assert_eq!(origin(&layered_conf, "workspace.work_dir"),
+                   "environment variable UDIX_WORKSPACE__WORK_DIR");
+    }
+}
@@ -0,0 +1,217 @@
+use std::collections::BTreeMap;
+use std::time::SystemTime;
+use serde::Serialize;
+use crate::apps::spec::AppSpec;
+use crate::conf::Conf;
+use crate::data::chromosome::Chromosome;
+use crate::date::format_duration_millis;
+use crate::error::Error;
+use crate::monitor;
+use crate::monitor::JobInfo;
+use crate::platform::Platform;
+use crate::report::print_rows;
+use crate::selection::Format;
+
+const OUTLIER_FACTOR: f64 = 5.0;
+const TOTAL: &str = "total";
+const OUTLIER: &str = "outlier";
+const WALL_TIME: &str = "wall time";
+const COST: &str = "cost";
+const UNKNOWN: &str = "unknown";
+
+#[derive(Serialize, Default)]
+struct CostRow {
+    group: String,
+    key: String,
+    n_jobs: usize,
+    total_cost: f64,
+    mean_cost: f64,
+    total_secs: i64,
+    mean_secs: i64,
+    outlier: String,
+}
+
+type JobValue = dyn Fn(&JobInfo) -> Option<f64>;
+
+#[derive(Default)]
+struct CostStats {
+    n_jobs: usize,
+    total_cost: f64,
+    n_timed: usize,
+    total_millis: i64,
+}
+
+impl CostStats {
+    fn add(&mut self, job: &JobInfo, now: i64) {
+        self.n_jobs += 1;
+        self.total_cost += job.total_price.unwrap_or(0.0);
+        if let Some(millis) = wall_time_millis(job, now) {
+            self.n_timed += 1;
+            self.total_millis += millis;
+        }
+    }
+    fn mean_cost(&self) -> f64 {
+        if self.n_jobs == 0 { 0.0 } else { self.total_cost / (self.n_jobs as f64) }
+    }
+    fn mean_millis(&self) -> i64 {
+        if self.n_timed == 0 { 0 } else { self.total_millis / (self.n_timed as i64) }
+    }
+    fn cost_row(&self, group: &str, key: &str) -> CostRow {
+        CostRow {
+            group: group.to_string(),
+            key: key.to_string(),
+            n_jobs: self.n_jobs,
+            total_cost: self.total_cost,
+            mean_cost: self.mean_cost(),
+            total_secs: self.total_millis / 1000,
+            mean_secs: self.mean_millis() / 1000,
+            outlier: String::new(),
+        }
+    }
+    fn row(&self, key: &str) -> String {
+        format!("{:>12} {:>6} {:>12.2} {:>10.2} {:>11} {:>10}", key, self.n_jobs,
+                self.total_cost, self.mean_cost(), format_duration_millis(self.total_millis),
+                format_duration_millis(self.mean_millis()))
+    }
+}
+
+fn wall_time_millis(job: &JobInfo, now: i64) -> Option<i64> {
+    job.started_running.map(|started| job.stopped_running.unwrap_or(now) - started)
+}
+
+fn option_key<T: ToString>(key: &Option<T>) -> String {
+    key.as_ref().map(|key| key.to_string()).unwrap_or_else(|| UNKNOWN.to_string())
+}
+
+fn string_keyed(stats_by_key: &BTreeMap<String, CostStats>) -> Vec<(String, &CostStats)> {
+    stats_by_key.iter().map(|(key, stats)| (key.clone(), stats)).collect()
+}
+
+fn median(mut values: Vec<f64>) -> Option<f64> {
+    values.sort_by(|value1, value2| value1.total_cmp(value2));
+    values.get(values.len() / 2).copied()
+}
+
+fn print_table(title: &str, stats_by_key: &[(String, &CostStats)]) {
+    println!("By {}:", title);
+    println!("{:>12} {:>6} {:>12} {:>10} {:>11} {:>10}", title, "jobs", "total cost", "mean cost",
+             "total time", "mean time");
+    for (key, stats) in stats_by_key {
+        println!("{}", stats.row(key));
+    }
+}
+
+fn find_outliers<'a>(jobs: &'a [JobInfo], value: &JobValue)
+                    -> Option<(f64, Vec<&'a JobInfo>)> {
+    let values: Vec<f64> = jobs.iter().filter_map(value).collect();
+    median(values).filter(|median| *median > 0.0).map(|median| {
+        let outliers: Vec<&JobInfo> =
+            jobs.iter().filter(|job| {
+                value(job).map(|value| value > OUTLIER_FACTOR * median).unwrap_or(false)
+            }).collect();
+        (median, outliers)
+    })
+}
+
+fn print_outliers(what: &str, jobs: &[JobInfo], value: &JobValue,
+                  format: &dyn Fn(f64) -> String) {
+    if let Some((median, outliers)) = find_outliers(jobs, value) {
+        if !outliers.is_empty() {
+            println!("Jobs with more than {} times the median {} of {} ({}):", OUTLIER_FACTOR,
+                     what, format(median), outliers.len());
+            for job in outliers {
+                let value = value(job).map(format).unwrap_or_default();
+                println!("  {} ({}): {}", job.name, job.id, value);
+            }
+        }
+    }
+}
+
+fn outlier_rows(jobs: &[JobInfo], now: i64) -> Vec<CostRow> {
+    let wall_time = move |job: &JobInfo| wall_time_millis(job, now).map(|millis| millis as f64);
+    let cost = |job: &JobInfo| job.total_price;
+    let values: [(&str, &JobValue); 2] = [(WALL_TIME, &wall_time), (COST, &cost)];
+    let mut flags_by_id: BTreeMap<&str, (&JobInfo, Vec<&str>)> = BTreeMap::new();
+    for (what, value) in values {
+        for job in find_outliers(jobs, value).map(|(_, outliers)| outliers).unwrap_or_default() {
+            flags_by_id.entry(job.id.as_str()).or_insert((job, Vec::new())).1.push(what)
+        }
+    }
+    flags_by_id.into_values().map(|(job, flags)| {
+        let mut stats = CostStats::default();
+        stats.add(job, now);
+        CostRow { outlier: flags.join(", "), ..stats.cost_row(OUTLIER, &job.name) }
+    }).collect()
+}
+
+pub(crate) fn report_costs(conf: &Conf, platform: &dyn Platform, app: &AppSpec,
+                           format: Format) -> Result<(), Error> {
+    let jobs = monitor::find_app_jobs(conf, platform, app)?.ledgered;
+    let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)?.as_millis() as i64;
+    let mut total = CostStats::default();
+    let mut by_chromosome: BTreeMap<Option<Chromosome>, CostStats> = BTreeMap::new();
+    let mut by_state: BTreeMap<String, CostStats> = BTreeMap::new();
+    let mut by_user: BTreeMap<String, CostStats> = BTreeMap::new();
+    for job in &jobs {
+        total.add(job, now);
+        by_chromosome.entry(app.parse_chromosome(&job.name)).or_default().add(job, now);
+        by_state.entry(job.state.to_string()).or_default().add(job, now);
+        by_user.entry(option_key(&job.launched_by)).or_default().add(job, now);
+    }
+    let chromosome_stats: Vec<(String, &CostStats)> =
+        by_chromosome.iter().filter(|(chromosome, _)| chromosome.is_some())
+            .chain(by_chromosome.get_key_value(&None))
+            .map(|(chromosome, stats)| (option_key(chromosome), stats)).collect();
+    let tables =
+        [("chromosome", chromosome_stats), ("state", string_keyed(&by_state)),
+            ("user", string_keyed(&by_user))];
+    let mut rows: Vec<CostRow> = vec![total.cost_row(TOTAL, TOTAL)];
+    for (group, stats_by_key) in &tables {
+        rows.extend(stats_by_key.iter().map(|(key, stats)| stats.cost_row(group, key)));
+    }
+    rows.extend(outlier_rows(&jobs, now));
+    print_rows(format, &rows, || {
+        println!("{} {} jobs cost {:.2} in total and {:.2} on average, and ran for {} in total \
+            and {} on average.", total.n_jobs, app.name, total.total_cost, total.mean_cost(),
+                 format_duration_millis(total.total_millis),
+                 format_duration_millis(total.mean_millis()));
+        if jobs.is_empty() {
+            return;
+        }
+        for (group, stats_by_key) in &tables {
+            print_table(group, stats_by_key);
+        }
+        print_outliers(WALL_TIME, &jobs,
+                       &move |job| wall_time_millis(job, now).map(|millis| millis as f64),
+                       &|millis| format_duration_millis(millis as i64));
+        print_outliers(COST, &jobs, &|job| job.total_price, &|cost| format!("{:.2}", cost));
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use crate::monitor::JobInfo;
+    use super::outlier_rows;
+
+    fn job(id: &str, price: f64, millis: i64) -> JobInfo {
+        serde_json::from_value(json!({
+            "id": id, "name": format!("vcfs2bed_{}", id), "state": "done", "totalPrice": price,
+            "startedRunning": 0, "stoppedRunning": millis
+        })).unwrap()
+    }
+
+    #[test]
+    fn outlier_rows_flag_jobs_far_above_the_median() {
+        let jobs = vec![job("a", 1.0, 60_000), job("b", 1.0, 60_000), job("c", 9.0, 60_000),
+                        job("d", 1.0, 900_000), job("e", 6.0, 600_000)];
+        let rows: Vec<(String, String, String)> =
+            outlier_rows(&jobs, 0).into_iter()
+                .map(|row| (row.group, row.key, row.outlier)).collect();
+        assert_eq!(rows, vec![
+            ("outlier".to_string(), "vcfs2bed_c".to_string(), "cost".to_string()),
+            ("outlier".to_string(), "vcfs2bed_d".to_string(), "wall time".to_string()),
+            ("outlier".to_string(), "vcfs2bed_e".to_string(), "wall time, cost".to_string()),
+        ]);
+    }
+}
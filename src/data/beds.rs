@@ -1,9 +1,12 @@
 use std::fmt::{Display, Formatter};
 use std::mem;
+use serde::Serialize;
 use crate::conf::Conf;
 use crate::data::chromosome::Chromosome;
-use crate::dx;
+use crate::platform::Platform;
 use crate::error::Error;
+use crate::report::print_rows;
+use crate::selection::Format;
 
 pub(crate) enum FileType {
     Bed,
@@ -23,6 +26,20 @@ pub(crate) struct BedBundlesOfChr {
     pub(crate) bed_bundles: Vec<BedBundle>,
 }
 
+#[derive(Serialize, Default)]
+struct BedBundleRow {
+    basename: String,
+    prefix: String,
+    chromosome: String,
+    i_block: usize,
+}
+
+#[derive(Serialize, Default)]
+struct BedChrSurvey {
+    chromosome: String,
+    n_bundles: usize,
+}
+
 impl BedBundle {
     fn cannot_parse(basename: &str) -> Error {
         Error::from(
@@ -125,13 +142,12 @@ impl FileMatchBuffer {
     fn is_empty(&self) -> bool { (!self.got_bed) && (!self.got_bim) && (!self.got_fam) }
 }
 
-fn get_bed_bundles(conf: &Conf) -> Result<Vec<BedBundle>, Error> {
-    let stdout = dx::capture_stdout(&["ls", conf.data.beds_dir.as_str()])?;
-    let mut lines: Vec<&str> = stdout.lines().collect();
+fn get_bed_bundles(conf: &Conf, platform: &dyn Platform) -> Result<Vec<BedBundle>, Error> {
+    let mut lines = platform.list_folder(&conf.in_project(&conf.data.beds_dir))?;
     lines.sort();
     let mut bed_bundles: Vec<BedBundle> = Vec::new();
     let mut file_match_buffer = FileMatchBuffer::new();
-    for line in lines {
+    for line in &lines {
         let bed_bundle_opt_res =
             if let Some(bed_basename) = line.strip_suffix(".bed") {
                 file_match_buffer.push(&FileType::Bed, bed_basename)
@@ -154,9 +170,10 @@ fn get_bed_bundles(conf: &Conf) -> Result<Vec<BedBundle>, Error> {
     }
 }
 
-pub(crate) fn get_bed_bundles_by_chrom(conf: &Conf) -> Result<Vec<BedBundlesOfChr>, Error> {
+pub(crate) fn get_bed_bundles_by_chrom(conf: &Conf, platform: &dyn Platform)
+                                       -> Result<Vec<BedBundlesOfChr>, Error> {
     let mut bed_bundles_of_chrs: Vec<BedBundlesOfChr> = Vec::new();
-    let mut bed_bundle_iter = get_bed_bundles(conf)?.into_iter();
+    let mut bed_bundle_iter = get_bed_bundles(conf, platform)?.into_iter();
     if let Some(bed_bundle) = bed_bundle_iter.next() {
         let mut chromosome = bed_bundle.chromosome;
         let mut bed_bundles_new: Vec<BedBundle> = vec!(bed_bundle);
@@ -176,17 +193,65 @@ pub(crate) fn get_bed_bundles_by_chrom(conf: &Conf) -> Result<Vec<BedBundlesOfCh
     Ok(bed_bundles_of_chrs)
 }
 
-pub(crate) fn list_beds(conf: &Conf) -> Result<(), Error> {
-    for bed_bundle in get_bed_bundles(conf)? {
-        println!("{}", bed_bundle)
-    }
-    Ok(())
+pub(crate) fn list_beds(conf: &Conf, platform: &dyn Platform, format: Format)
+                        -> Result<(), Error> {
+    let bed_bundles = get_bed_bundles(conf, platform)?;
+    let rows: Vec<BedBundleRow> =
+        bed_bundles.iter().map(|bed_bundle| {
+            BedBundleRow {
+                basename: bed_bundle.basename(),
+                prefix: bed_bundle.prefix.clone(),
+                chromosome: bed_bundle.chromosome.to_string(),
+                i_block: bed_bundle.i_block,
+            }
+        }).collect();
+    print_rows(format, &rows, || {
+        for bed_bundle in &bed_bundles {
+            println!("{}", bed_bundle)
+        }
+    })
+}
+
+pub(crate) fn survey_beds(conf: &Conf, platform: &dyn Platform, format: Format)
+                          -> Result<(), Error> {
+    let rows: Vec<BedChrSurvey> =
+        get_bed_bundles_by_chrom(conf, platform)?.into_iter().map(|bed_bundles_of_chr| {
+            let chromosome = bed_bundles_of_chr.chromosome.to_string();
+            BedChrSurvey { chromosome, n_bundles: bed_bundles_of_chr.bed_bundles.len() }
+        }).collect();
+    print_rows(format, &rows, || {
+        for row in &rows {
+            println!("For chromosome {}, we have {} BED bundles.", row.chromosome,
+                     row.n_bundles)
+        }
+    })
 }
 
-pub(crate) fn survey_beds(conf: &Conf) -> Result<(), Error> {
-    for bed_bundle_of_chr in get_bed_bundles_by_chrom(conf)? {
-        println!("For chromosome {}, we have {} BED bundles.", bed_bundle_of_chr.chromosome,
-                 bed_bundle_of_chr.bed_bundles.len())
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use crate::testing::{cassette_conf, replay_cassette};
+    use super::{get_bed_bundles_by_chrom, FileType};
+
+    #[test]
+    fn group_recorded_bed_folder_by_chromosome() {
+        let conf = cassette_conf("/udix/beds/", Path::new("/nonexistent"));
+        let platform = replay_cassette("bed_folder.jsonl");
+        let bundles_by_chrom = get_bed_bundles_by_chrom(&conf, &platform).unwrap();
+        let chromosomes: Vec<String> =
+            bundles_by_chrom.iter().map(|bundles| bundles.chromosome.to_string()).collect();
+        assert_eq!(chromosomes, vec!["1", "2", "10", "X"]);
+        let chr1_beds: Vec<String> =
+            bundles_by_chrom[0].bed_bundles.iter()
+                .map(|bundle| bundle.file_name(&FileType::Bed)).collect();
+        assert_eq!(chr1_beds, vec!["vcfs2bed_c1_b0.bed", "vcfs2bed_c1_b1.bed"]);
+    }
+
+    #[test]
+    fn incomplete_recorded_bed_bundle_is_an_error() {
+        let conf = cassette_conf("/udix/beds_partial/", Path::new("/nonexistent"));
+        let platform = replay_cassette("bed_folder_incomplete.jsonl");
+        let error = get_bed_bundles_by_chrom(&conf, &platform).err().unwrap();
+        assert!(error.to_string().contains("no vcfs2bed_c2_b0.bim"), "{}", error);
     }
-    Ok(())
-}
\ No newline at end of file
+}
@@ -1,7 +1,7 @@
 use std::fmt::{Display, Formatter};
 use crate::error::Error;
 
-#[derive(Ord, PartialOrd, Eq, PartialEq, Copy, Clone)]
+#[derive(Ord, PartialOrd, Eq, PartialEq, Copy, Clone, Debug)]
 pub(crate) enum Chromosome {
     Auto(u8),
     Allo(char),
@@ -1,9 +1,12 @@
 use std::mem;
 use std::mem::replace;
+use serde::Serialize;
 use crate::conf::Conf;
 use crate::data::chromosome::Chromosome;
 use crate::error::Error;
-use crate::dx;
+use crate::platform::Platform;
+use crate::report::print_rows;
+use crate::selection::Format;
 
 #[derive(Ord, PartialOrd, Eq, PartialEq)]
 pub(crate) struct VcfFileKey {
@@ -26,6 +29,21 @@ pub(crate) struct VcfFilesOfChr {
     pub(crate) blocks: Vec<VcfFileBlock>
 }
 
+#[derive(Serialize, Default)]
+struct VcfFileRow {
+    name: String,
+    chromosome: String,
+    i_file: usize,
+    i_block: usize,
+}
+
+#[derive(Serialize, Default)]
+struct VcfChrSurvey {
+    chromosome: String,
+    n_files: usize,
+    n_blocks: usize,
+}
+
 const BLOCK_SIZE: usize = 100;
 
 impl VcfFileKey {
@@ -59,11 +77,11 @@ impl VcfFile {
     }
 }
 
-fn get_vcf_files(conf: &Conf) -> Result<Vec<VcfFile>, Error> {
-    let stdout = dx::capture_stdout(&["ls", conf.data.vcfs_dir.as_str()])?;
+fn get_vcf_files(conf: &Conf, platform: &dyn Platform) -> Result<Vec<VcfFile>, Error> {
+    let names = platform.list_folder(&conf.in_project(&conf.data.vcfs_dir))?;
     let mut vcf_files: Vec<VcfFile> = Vec::new();
-    for line in stdout.lines() {
-        match VcfFile::parse_if_vcf(line)? {
+    for name in names {
+        match VcfFile::parse_if_vcf(&name)? {
             None => {}
             Some(vcf_file) => { vcf_files.push(vcf_file) }
         }
@@ -71,14 +89,15 @@ fn get_vcf_files(conf: &Conf) -> Result<Vec<VcfFile>, Error> {
     Ok(vcf_files)
 }
 
-fn get_vcf_files_sorted(conf: &Conf) -> Result<Vec<VcfFile>, Error> {
-    let mut vcf_files = get_vcf_files(conf)?;
+fn get_vcf_files_sorted(conf: &Conf, platform: &dyn Platform) -> Result<Vec<VcfFile>, Error> {
+    let mut vcf_files = get_vcf_files(conf, platform)?;
     vcf_files.sort_by(|file1, file2| file1.key.cmp(&file2.key));
     Ok(vcf_files)
 }
 
-pub(crate) fn group_vcf_files(conf: &Conf) -> Result<Vec<VcfFilesOfChr>, Error> {
-    let mut files =  get_vcf_files_sorted(conf)?.into_iter();
+pub(crate) fn group_vcf_files(conf: &Conf, platform: &dyn Platform)
+                              -> Result<Vec<VcfFilesOfChr>, Error> {
+    let mut files =  get_vcf_files_sorted(conf, platform)?.into_iter();
     let mut files_by_chr: Vec<VcfFilesOfChr> = Vec::new();
     if let Some(file) = files.next() {
         let mut i_block = file.key.i_block();
@@ -114,34 +133,69 @@ pub(crate) fn group_vcf_files(conf: &Conf) -> Result<Vec<VcfFilesOfChr>, Error>
     Ok(files_by_chr)
 }
 
-pub(crate) fn list_vcfs(conf: &Conf) -> Result<(), Error> {
-    let vcf_files = get_vcf_files_sorted(conf)?;
-    for vcf in vcf_files {
-        println!("{}", vcf.name);
-    }
-    Ok(())
+pub(crate) fn list_vcfs(conf: &Conf, platform: &dyn Platform, format: Format)
+                        -> Result<(), Error> {
+    let rows: Vec<VcfFileRow> =
+        get_vcf_files_sorted(conf, platform)?.into_iter().map(|vcf| {
+            let chromosome = vcf.key.chromosome.to_string();
+            let i_block = vcf.key.i_block();
+            VcfFileRow { name: vcf.name, chromosome, i_file: vcf.key.i_file, i_block }
+        }).collect();
+    print_rows(format, &rows, || {
+        for row in &rows {
+            println!("{}", row.name);
+        }
+    })
 }
 
-pub(crate) fn survey_vcfs(conf: &Conf) -> Result<(), Error> {
-    println!("VCF files are here: {}", conf.data.vcfs_dir);
-    let files_by_chr = group_vcf_files(conf)?;
-    let mut n_files: usize = 0;
-    let mut n_blocks: usize = 0;
-    let mut n_chrs: usize = 0;
+pub(crate) fn survey_vcfs(conf: &Conf, platform: &dyn Platform, format: Format)
+                          -> Result<(), Error> {
+    let files_by_chr = group_vcf_files(conf, platform)?;
+    let mut rows: Vec<VcfChrSurvey> = Vec::new();
     for files_of_chr in files_by_chr {
-        let mut n_blocks_for_chr: usize = 0;
-        let mut n_files_for_chr: usize = 0;
-        for block in &files_of_chr.blocks {
-            n_blocks_for_chr += 1;
-            n_files_for_chr += block.files.len()
-        }
-        println!("Chromosome {} has {} files in {} blocks", files_of_chr.chromosome,
-                 n_files_for_chr, n_blocks_for_chr);
-        n_chrs += 1;
-        n_blocks += n_blocks_for_chr;
-        n_files += n_files_for_chr;
+        let n_blocks = files_of_chr.blocks.len();
+        let n_files = files_of_chr.blocks.iter().map(|block| block.files.len()).sum();
+        let chromosome = files_of_chr.chromosome.to_string();
+        rows.push(VcfChrSurvey { chromosome, n_files, n_blocks });
     }
-    println!("There are {} files in {} blocks and {} chromosomes.", n_files, n_blocks, n_chrs);
-    Ok(())
+    print_rows(format, &rows, || {
+        println!("VCF files are here: {}", conf.data.vcfs_dir);
+        for row in &rows {
+            println!("Chromosome {} has {} files in {} blocks", row.chromosome, row.n_files,
+                     row.n_blocks);
+        }
+        let n_files: usize = rows.iter().map(|row| row.n_files).sum();
+        let n_blocks: usize = rows.iter().map(|row| row.n_blocks).sum();
+        println!("There are {} files in {} blocks and {} chromosomes.", n_files, n_blocks,
+                 rows.len());
+    })
 }
 
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use crate::testing::{cassette_conf, replay_cassette};
+    use super::group_vcf_files;
+
+    #[test]
+    fn group_recorded_vcf_folder_by_chromosome_and_block() {
+        let conf = cassette_conf("/udix/beds/", Path::new("/nonexistent"));
+        let platform = replay_cassette("vcf_folder.jsonl");
+        let groups = group_vcf_files(&conf, &platform).unwrap();
+        let summary: Vec<(String, Vec<(usize, usize)>)> =
+            groups.iter().map(|group| {
+                let blocks =
+                    group.blocks.iter().map(|block| (block.i_block, block.files.len())).collect();
+                (group.chromosome.to_string(), blocks)
+            }).collect();
+        assert_eq!(summary, vec![
+            ("1".to_string(), vec![(0, 3), (1, 2)]),
+            ("2".to_string(), vec![(0, 1)]),
+            ("10".to_string(), vec![(0, 1)]),
+            ("X".to_string(), vec![(0, 2)]),
+        ]);
+        let names: Vec<&str> =
+            groups[0].blocks[1].files.iter().map(|file| file.name.as_str()).collect();
+        assert_eq!(names, vec!["ukb23157_c1_b100_v1.vcf.gz", "ukb23157_c1_b101_v1.vcf.gz"]);
+    }
+}
@@ -0,0 +1,149 @@
+use std::time::SystemTime;
+use crate::error::Error;
+
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let month_shifted = if month > 2 { month - 3 } else { month + 9 };
+    let day_of_year = (153 * month_shifted + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+const MILLIS_PER_SECOND: i64 = 1000;
+const MILLIS_PER_DAY: i64 = 24 * 3600 * MILLIS_PER_SECOND;
+
+fn cannot_parse(string: &str) -> Error {
+    Error::from(format!("Cannot parse date '{}', expected YYYY-MM-DD, YYYY-MM-DD HH:MM[:SS], \
+    milliseconds since the epoch or a time ago like -2w.", string))
+}
+
+fn unit_millis(unit: char) -> Option<i64> {
+    match unit {
+        's' => { Some(MILLIS_PER_SECOND) }
+        'm' => { Some(60 * MILLIS_PER_SECOND) }
+        'h' => { Some(3600 * MILLIS_PER_SECOND) }
+        'd' => { Some(MILLIS_PER_DAY) }
+        'w' => { Some(7 * MILLIS_PER_DAY) }
+        'M' => { Some(30 * MILLIS_PER_DAY) }
+        'y' => { Some(365 * MILLIS_PER_DAY) }
+        _ => { None }
+    }
+}
+
+fn parse_ago_millis(ago: &str) -> Option<i64> {
+    match ago.char_indices().last()? {
+        (i_unit, unit) if unit.is_ascii_alphabetic() => {
+            Some(ago[..i_unit].parse::<i64>().ok()? * unit_millis(unit)?)
+        }
+        _ => { ago.parse::<i64>().ok() }
+    }
+}
+
+fn parse_numbers(string: &str, separator: char, n_min: usize) -> Option<Vec<i64>> {
+    let numbers =
+        string.split(separator).map(|part| part.parse::<i64>().ok())
+            .collect::<Option<Vec<i64>>>()?;
+    if numbers.len() < n_min || numbers.len() > 3 {
+        None
+    } else {
+        Some(numbers)
+    }
+}
+
+fn parse_date_time_millis(string: &str) -> Option<i64> {
+    let (date, time) =
+        match string.split_once([' ', 'T']) {
+            None => { (string, None) }
+            Some((date, time)) => { (date, Some(time.trim())) }
+        };
+    let date = parse_numbers(date, '-', 3)?;
+    let (year, month, day) = (date[0], date[1], date[2]);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    let time =
+        match time {
+            None => { vec![0, 0, 0] }
+            Some(time) => { parse_numbers(time, ':', 2)? }
+        };
+    let (hours, minutes, seconds) = (time[0], time[1], time.get(2).copied().unwrap_or(0));
+    if !(0..24).contains(&hours) || !(0..60).contains(&minutes) || !(0..60).contains(&seconds) {
+        return None;
+    }
+    Some(days_from_civil(year, month, day) * MILLIS_PER_DAY
+        + ((hours * 60 + minutes) * 60 + seconds) * MILLIS_PER_SECOND)
+}
+
+fn parse_date_millis_at(string: &str, now_millis: i64) -> Result<i64, Error> {
+    let trimmed = string.trim();
+    let millis =
+        if let Some(ago) = trimmed.strip_prefix('-') {
+            parse_ago_millis(ago).map(|ago_millis| now_millis - ago_millis)
+        } else if trimmed.chars().all(|c| c.is_ascii_digit()) {
+            trimmed.parse::<i64>().ok()
+        } else {
+            parse_date_time_millis(trimmed)
+        };
+    millis.ok_or_else(|| cannot_parse(string))
+}
+
+pub(crate) fn parse_date_millis(string: &str) -> Result<i64, Error> {
+    let now_millis = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)?.as_millis() as i64;
+    parse_date_millis_at(string, now_millis)
+}
+
+pub(crate) fn format_duration_millis(millis: i64) -> String {
+    let minutes = millis / 60_000;
+    if minutes < 60 {
+        format!("{}m", minutes)
+    } else {
+        format!("{}h {:02}m", minutes / 60, minutes % 60)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_duration_millis, parse_date_millis, parse_date_millis_at};
+
+    const DAY: i64 = 24 * 3600 * 1000;
+
+    #[test]
+    fn parse_dates() {
+        assert_eq!(parse_date_millis("1970-01-01").unwrap(), 0);
+        assert_eq!(parse_date_millis("1970-01-02").unwrap(), DAY);
+        assert_eq!(parse_date_millis("2024-03-01").unwrap(), 1709251200000);
+        assert_eq!(parse_date_millis(" 2024-03-01 ").unwrap(), 1709251200000);
+    }
+
+    #[test]
+    fn parse_date_times() {
+        assert_eq!(parse_date_millis("2024-03-01 12:30").unwrap(), 1709296200000);
+        assert_eq!(parse_date_millis("2024-03-01T12:30:15").unwrap(), 1709296215000);
+    }
+
+    #[test]
+    fn parse_timestamps_and_times_ago() {
+        assert_eq!(parse_date_millis("1709251200000").unwrap(), 1709251200000);
+        let now = 100 * DAY;
+        assert_eq!(parse_date_millis_at("-2d", now).unwrap(), 98 * DAY);
+        assert_eq!(parse_date_millis_at("-1w", now).unwrap(), 93 * DAY);
+        assert_eq!(parse_date_millis_at("-3h", now).unwrap(), now - 3 * 3600 * 1000);
+        assert_eq!(parse_date_millis_at("-5000", now).unwrap(), now - 5000);
+    }
+
+    #[test]
+    fn reject_malformed_dates() {
+        for string in ["", "yesterday", "2024-13-01", "2024-02-32", "2024-02", "2024-02-02-02",
+            "2024-02-02 25:00", "-2q", "-d"] {
+            assert!(parse_date_millis(string).is_err(), "{}", string);
+        }
+    }
+
+    #[test]
+    fn format_durations() {
+        assert_eq!(format_duration_millis(59 * 60 * 1000), "59m");
+        assert_eq!(format_duration_millis(125 * 60 * 1000), "2h 05m");
+    }
+}
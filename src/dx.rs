@@ -1,71 +1,275 @@
+use std::fs;
 use std::path::Path;
-use std::process::{Command, Output};
-use crate::error::Error;
+use std::process::Command;
+use std::thread;
+use std::time::Duration;
+use crate::error::{DxErrorKind, Error, ErrorKind};
 use std::str;
-use serde::Serialize;
 use serde_json::Value;
+use crate::dx::cassette::{Interaction, LocalFiles, Player, Recorder};
+use crate::platform::{AppRun, FileInfo, JobQuery, OnJob, page_through_jobs, path_to_str,
+                      Platform};
+use crate::retry;
+
+mod cassette;
 
 const DX: &str = "dx";
+const PROJECT_CONTEXT_ID: &str = "DX_PROJECT_CONTEXT_ID";
 
-pub(crate) fn capture_stdout(args: &[&str]) -> Result<String, Error> {
-    let output = run(args)?;
-    Ok(String::from_utf8(output.stdout)?)
+pub(crate) enum DxMode {
+    Live,
+    Record(Recorder),
+    Replay(Player),
 }
 
-pub(crate) fn run(args: &[&str]) -> Result<Output, Error> {
-    let output = Command::new(DX).args(args).output()?;
-    if output.status.success() {
-        Ok(output)
-    } else {
-        let message =
-            format!("dx failed ({}): {}", output.status,
-                    String::from_utf8_lossy(&output.stderr));
-        Err(Error::from(message))
+pub(crate) struct DxCli {
+    mode: DxMode,
+}
+
+pub(crate) struct DxError {
+    pub(crate) kind: DxErrorKind,
+    pub(crate) message: String,
+}
+
+mod patterns {
+    pub(crate) const AUTH_EXPIRED: [&str; 4] =
+        ["InvalidAuthentication", "token could not be found", "token has expired",
+            "Please log in"];
+    pub(crate) const NOT_FOUND: [&str; 4] =
+        ["ResourceNotFound", "could not be found", "Could not resolve", "does not exist"];
+    pub(crate) const PERMISSION_DENIED: [&str; 2] = ["PermissionDenied", "Permission denied"];
+    pub(crate) const RATE_LIMITED: [&str; 3] =
+        ["RateLimitConditional", "Too Many Requests", "rate limit"];
+    pub(crate) const NETWORK: [&str; 9] =
+        ["ConnectionError", "Connection reset", "Connection refused", "Max retries exceeded",
+            "timed out", "ServiceUnavailable", "Service Unavailable", "Gateway Time",
+            "Temporary failure in name resolution"];
+}
+
+impl DxError {
+    pub(crate) fn classify(message: &str) -> DxErrorKind {
+        let matches_any = |patterns: &[&str]| {
+            patterns.iter().any(|pattern| message.contains(pattern))
+        };
+        if matches_any(&patterns::AUTH_EXPIRED) {
+            DxErrorKind::AuthExpired
+        } else if matches_any(&patterns::PERMISSION_DENIED) {
+            DxErrorKind::PermissionDenied
+        } else if matches_any(&patterns::RATE_LIMITED) {
+            DxErrorKind::RateLimited
+        } else if matches_any(&patterns::NETWORK) {
+            DxErrorKind::Network
+        } else if matches_any(&patterns::NOT_FOUND) {
+            DxErrorKind::NotFound
+        } else {
+            DxErrorKind::Other
+        }
+    }
+    fn from_interaction(interaction: &Interaction) -> DxError {
+        let kind = DxError::classify(&interaction.stderr);
+        let status =
+            match interaction.status {
+                None => { "killed by signal".to_string() }
+                Some(code) => { format!("exit status: {}", code) }
+            };
+        let message = format!("dx failed ({}): {}", status, interaction.stderr.trim());
+        DxError { kind, message }
     }
 }
 
-pub(crate) fn pwd() -> Result<String, Error> {
-    capture_stdout(&["pwd"])
+impl From<DxError> for Error {
+    fn from(dx_error: DxError) -> Self {
+        Error::new(ErrorKind::Dx(dx_error.kind), dx_error.message, None)
+    }
 }
 
-pub(crate) fn get_project() -> Result<String, Error> {
-    let pwd = pwd()?;
-    pwd.split(':').next().map(|s| s.to_string())
-        .ok_or_else(|| { Error::from(format!("Could not parse project from '{}'.", pwd)) })
+fn run_command(args: &[String], output_file: Option<&Path>) -> Result<Interaction, Error> {
+    let output = Command::new(DX).args(args).output()?;
+    let args = args.to_vec();
+    let stdout = String::from_utf8(output.stdout)?;
+    let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+    let status = output.status.code();
+    let output_file =
+        match output_file {
+            Some(output_file) if output.status.success() => {
+                Some(fs::read_to_string(output_file)?)
+            }
+            _ => { None }
+        };
+    Ok(Interaction { args, stdout, stderr, status, input_file: None, output_file })
 }
 
-#[derive(Serialize)]
-pub(crate) struct DnaNexusLink {
-    id: String,
-    project: String,
+impl DxCli {
+    pub(crate) fn new(mode: DxMode) -> DxCli {
+        DxCli { mode }
+    }
+    fn capture_stdout(&self, args: &[&str]) -> Result<String, Error> {
+        Ok(self.run(args)?.stdout)
+    }
+    fn run(&self, args: &[&str]) -> Result<Interaction, Error> {
+        self.run_retrying(args, LocalFiles::default(), retry::is_transient)
+    }
+    fn run_retrying(&self, args: &[&str], local_files: LocalFiles,
+                    should_retry: fn(DxErrorKind) -> bool) -> Result<Interaction, Error> {
+        let description = format!("{} {}", DX, args.first().unwrap_or(&""));
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        let pause: fn(Duration) =
+            match self.mode {
+                DxMode::Replay(_) => { |_| {} }
+                _ => { thread::sleep }
+            };
+        retry::with_retries(&description, should_retry, pause, || {
+            self.run_once(&args, local_files)
+        })
+    }
+    fn run_once(&self, args: &[String], local_files: LocalFiles)
+                -> Result<Interaction, Error> {
+        let interaction =
+            match &self.mode {
+                DxMode::Live => { run_command(args, local_files.output)? }
+                DxMode::Record(recorder) => {
+                    let interaction = run_command(args, local_files.output)?;
+                    recorder.record(&interaction, local_files)?;
+                    interaction
+                }
+                DxMode::Replay(player) => { player.play(args, local_files)? }
+            };
+        if interaction.status == Some(0) {
+            Ok(interaction)
+        } else {
+            Err(Error::from(DxError::from_interaction(&interaction)))
+        }
+    }
+    fn env_var(&self, name: &str) -> Result<Option<String>, Error> {
+        let stdout = self.capture_stdout(&["env", "--bash"])?;
+        let value =
+            stdout.lines()
+                .filter_map(|line| line.trim().trim_start_matches("export ").split_once('='))
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| value.trim().trim_matches(['"', '\'']).to_string())
+                .filter(|value| !value.is_empty());
+        Ok(value)
+    }
+}
+
+pub(crate) fn record(cassette: &Path) -> Result<DxCli, Error> {
+    Ok(DxCli::new(DxMode::Record(Recorder::create(cassette)?)))
 }
 
-#[derive(Serialize)]
-pub(crate) struct WrappedDnaNexusLink {
-    #[serde(rename = "$dnanexus_link")]
-    dnanexus_link: DnaNexusLink,
+pub(crate) fn replay(cassette: &Path) -> Result<DxCli, Error> {
+    Ok(DxCli::new(DxMode::Replay(Player::load(cassette)?)))
 }
 
-pub(crate) fn get_dna_nexus_link(path: &Path) -> Result<DnaNexusLink, Error> {
-    let path_str =
-        path.to_str().ok_or_else(|| {
-            Error::from(format!("Could not convert path '{}' to string.",
-                                path.to_string_lossy()))
-        })?;
-    let json_string = capture_stdout(&["describe", "--json", path_str])?;
-    let json_value: Value = serde_json::from_str(json_string.as_str())?;
-    let id =
-        json_value["id"].as_str().ok_or_else(|| {
-            Error::from(format!("Could not get id for '{}'", path.to_string_lossy()))
-        })?.to_string();
-    let project =
-        json_value["project"].as_str().ok_or_else(|| {
-            Error::from(format!("Could not get project for '{}'", path.to_string_lossy()))
-        })?.to_string();
-    Ok(DnaNexusLink { id, project })
+impl Platform for DxCli {
+    fn get_project(&self) -> Result<String, Error> {
+        self.env_var(PROJECT_CONTEXT_ID)?.ok_or_else(|| {
+            Error::from(format!("No project selected in dx ({} is not set). Run 'dx select' or \
+            set workspace.project.", PROJECT_CONTEXT_ID))
+        })
+    }
+
+    fn list_folder(&self, folder: &str) -> Result<Vec<String>, Error> {
+        let stdout = self.capture_stdout(&["ls", folder])?;
+        Ok(stdout.lines().map(|line| line.to_string()).collect())
+    }
+
+    fn find_files(&self, folder: &str) -> Result<Vec<FileInfo>, Error> {
+        let json_string =
+            self.capture_stdout(&["find", "data", "--class", "file", "--path", folder,
+                "--norecurse", "--json"])?;
+        let json_value: Value = serde_json::from_str(json_string.as_str())?;
+        let mut files: Vec<FileInfo> = Vec::new();
+        if let Value::Array(items) = json_value {
+            for mut item in items {
+                files.push(serde_json::from_value(item["describe"].take())?)
+            }
+        }
+        Ok(files)
+    }
+
+    fn get_executable_class(&self, path: &str) -> Result<String, Error> {
+        let json_string = self.capture_stdout(&["describe", "--json", path])?;
+        let json_value: Value = serde_json::from_str(json_string.as_str())?;
+        json_value["class"].as_str().map(|class| class.to_string()).ok_or_else(|| {
+            Error::from(format!("Could not get class of '{}'.", path))
+        })
+    }
+
+    fn find_jobs(&self, query: &JobQuery, on_job: &mut OnJob) -> Result<(), Error> {
+        page_through_jobs(query, on_job, &|input| {
+            let input = input.to_string();
+            let output = self.capture_stdout(&["api", "system", "findJobs", &input])?;
+            Ok(serde_json::from_str(&output)?)
+        })
+    }
+
+    fn run_app(&self, app_run: &AppRun) -> Result<String, Error> {
+        let inputs_file_arg = path_to_str(app_run.inputs_file)?;
+        let mut args: Vec<&str> =
+            vec!["run", "--name", app_run.name, "--input-json-file", inputs_file_arg,
+                 "--folder", app_run.folder, "--instance-type", app_run.instance_type, "--brief",
+                 "--yes"];
+        args.extend(app_run.extra_args.iter().map(|arg| arg.as_str()));
+        args.push(app_run.app_path);
+        let local_files = LocalFiles { input: Some(app_run.inputs_file), output: None };
+        let interaction = self.run_retrying(&args, local_files, retry::is_rejected)?;
+        Ok(interaction.stdout.trim().to_string())
+    }
+
+    fn job_log(&self, job_id: &str) -> Result<String, Error> {
+        self.capture_stdout(&["watch", job_id, "--no-job-info", "--no-wait"])
+    }
+
+    fn terminate_job(&self, job_id: &str) -> Result<(), Error> {
+        self.run(&["terminate", job_id])?;
+        Ok(())
+    }
+
+    fn download(&self, remote_path: &str, local_file: &Path) -> Result<(), Error> {
+        let local_file_str = path_to_str(local_file)?;
+        self.run_retrying(&["download", remote_path, "--overwrite", "--output", local_file_str],
+                          LocalFiles { input: None, output: Some(local_file) },
+                          retry::is_transient)?;
+        Ok(())
+    }
+
+    fn upload(&self, local_file: &Path, remote_path: &str) -> Result<String, Error> {
+        let local_file_str = path_to_str(local_file)?;
+        let path_arg = format!("{}:{}", self.get_project()?, remote_path);
+        let interaction =
+            self.run_retrying(&["upload", local_file_str, "--path", path_arg.as_str(),
+                "--brief"], LocalFiles { input: Some(local_file), output: None },
+                              retry::is_rejected)?;
+        Ok(interaction.stdout.trim().to_string())
+    }
+
+    fn remove_files(&self, ids: &[String]) -> Result<(), Error> {
+        let mut args: Vec<&str> = vec!["rm"];
+        args.extend(ids.iter().map(|id| id.as_str()));
+        self.run(&args)?;
+        Ok(())
+    }
 }
 
-pub(crate) fn get_wrapped_dna_nexus_link(path: &Path) -> Result<WrappedDnaNexusLink, Error> {
-    let dnanexus_link = get_dna_nexus_link(path)?;
-    Ok(WrappedDnaNexusLink { dnanexus_link })
+#[cfg(test)]
+mod tests {
+    use crate::error::DxErrorKind;
+    use super::DxError;
+
+    #[test]
+    fn classify_dx_errors() {
+        let cases = [
+            ("InvalidAuthentication: the token could not be found", DxErrorKind::AuthExpired),
+            ("PermissionDenied: VIEW permission required", DxErrorKind::PermissionDenied),
+            ("429 Too Many Requests", DxErrorKind::RateLimited),
+            ("ConnectionError: Connection reset by peer", DxErrorKind::Network),
+            ("Temporary failure in name resolution", DxErrorKind::Network),
+            ("ResourceNotFound: The specified folder could not be found", DxErrorKind::NotFound),
+            ("Could not resolve \"/apps/vcfs2bed\"", DxErrorKind::NotFound),
+            ("Something else went wrong", DxErrorKind::Other),
+        ];
+        for (message, kind) in cases {
+            assert_eq!(DxError::classify(message), kind, "{}", message);
+        }
+    }
 }
@@ -0,0 +1,230 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::error::Error;
+use crate::platform::path_to_str;
+
+mod placeholders {
+    pub(crate) const INPUT_FILE: &str = "<input_file>";
+    pub(crate) const OUTPUT_FILE: &str = "<output_file>";
+    pub(crate) const CREATED_AFTER: &str = "<created_after>";
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct Interaction {
+    pub(crate) args: Vec<String>,
+    pub(crate) stdout: String,
+    pub(crate) stderr: String,
+    pub(crate) status: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) input_file: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) output_file: Option<String>,
+}
+
+#[derive(Clone, Copy, Default)]
+pub(crate) struct LocalFiles<'a> {
+    pub(crate) input: Option<&'a Path>,
+    pub(crate) output: Option<&'a Path>,
+}
+
+type InteractionKey = (Vec<String>, Option<String>);
+
+fn pin_created_after(arg: &str) -> Option<String> {
+    let mut input: Value = serde_json::from_str(arg).ok()?;
+    *input.get_mut("created")?.get_mut("after")? = Value::from(placeholders::CREATED_AFTER);
+    Some(input.to_string())
+}
+
+pub(crate) struct Recorder {
+    file: RefCell<File>,
+}
+
+pub(crate) struct Player {
+    cassette: String,
+    interactions: RefCell<HashMap<InteractionKey, VecDeque<Interaction>>>,
+}
+
+impl LocalFiles<'_> {
+    fn normalize_args(&self, args: &[String]) -> Result<Vec<String>, Error> {
+        let input = self.input.map(path_to_str).transpose()?;
+        let output = self.output.map(path_to_str).transpose()?;
+        let args =
+            args.iter().map(|arg| {
+                if Some(arg.as_str()) == input {
+                    placeholders::INPUT_FILE.to_string()
+                } else if Some(arg.as_str()) == output {
+                    placeholders::OUTPUT_FILE.to_string()
+                } else {
+                    pin_created_after(arg).unwrap_or(arg.clone())
+                }
+            }).collect();
+        Ok(args)
+    }
+    fn key(&self, args: &[String]) -> Result<InteractionKey, Error> {
+        let input_file = self.input.map(fs::read_to_string).transpose()?;
+        Ok((self.normalize_args(args)?, input_file))
+    }
+}
+
+impl Recorder {
+    pub(crate) fn create(cassette: &Path) -> Result<Recorder, Error> {
+        if let Some(parent) = cassette.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(cassette)?;
+        Ok(Recorder { file: RefCell::new(file) })
+    }
+    pub(crate) fn record(&self, interaction: &Interaction, local_files: LocalFiles)
+                         -> Result<(), Error> {
+        let (args, input_file) = local_files.key(&interaction.args)?;
+        let interaction = Interaction { args, input_file, ..interaction.clone() };
+        let mut file = self.file.borrow_mut();
+        writeln!(file, "{}", serde_json::to_string(&interaction)?)?;
+        file.flush()?;
+        Ok(())
+    }
+}
+
+impl Player {
+    pub(crate) fn load(cassette: &Path) -> Result<Player, Error> {
+        let mut interactions: HashMap<InteractionKey, VecDeque<Interaction>> = HashMap::new();
+        for line in fs::read_to_string(cassette)?.lines() {
+            if !line.trim().is_empty() {
+                let interaction: Interaction = serde_json::from_str(line)?;
+                let key = (interaction.args.clone(), interaction.input_file.clone());
+                interactions.entry(key).or_default().push_back(interaction);
+            }
+        }
+        let cassette = cassette.to_string_lossy().to_string();
+        Ok(Player { cassette, interactions: RefCell::new(interactions) })
+    }
+    pub(crate) fn play(&self, args: &[String], local_files: LocalFiles)
+                       -> Result<Interaction, Error> {
+        let key = local_files.key(args)?;
+        let mut interactions = self.interactions.borrow_mut();
+        let queue =
+            interactions.get_mut(&key).ok_or_else(|| {
+                Error::from(format!("No interaction for 'dx {}' recorded in {}.", args.join(" "),
+                                    self.cassette))
+            })?;
+        let interaction =
+            if queue.len() > 1 {
+                queue.pop_front()
+            } else {
+                queue.front().cloned()
+            };
+        let interaction =
+            interaction.ok_or_else(|| {
+                Error::from(format!("Ran out of interactions for 'dx {}' in {}.", args.join(" "),
+                                    self.cassette))
+            })?;
+        if let (Some(output), Some(contents)) = (local_files.output, &interaction.output_file) {
+            fs::write(output, contents)?;
+        }
+        Ok(interaction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+    use crate::testing::TempDir;
+    use super::{Interaction, LocalFiles, Player, Recorder};
+
+    fn interaction(args: &[&str], stdout: &str, output_file: Option<&str>) -> Interaction {
+        Interaction {
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            stdout: stdout.to_string(),
+            stderr: String::new(),
+            status: Some(0),
+            input_file: None,
+            output_file: output_file.map(|contents| contents.to_string()),
+        }
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn path_str(path: &Path) -> &str {
+        path.to_str().unwrap()
+    }
+
+    #[test]
+    fn replay_matches_local_files_by_role_and_content() {
+        let dir = TempDir::new("cassette_roundtrip");
+        let cassette = dir.path.join("cassette.jsonl");
+        let recorded_inputs = dir.path.join("recorded").join("inputs_a");
+        fs::create_dir_all(recorded_inputs.parent().unwrap()).unwrap();
+        fs::write(&recorded_inputs, "{\"a\": 1}").unwrap();
+        let recorder = Recorder::create(&cassette).unwrap();
+        let run_args = ["run", "app", "-f", path_str(&recorded_inputs)];
+        let inputs = LocalFiles { input: Some(&recorded_inputs), output: None };
+        recorder.record(&interaction(&run_args, "job-1", None), inputs).unwrap();
+        let recorded_download = dir.path.join("recorded").join("log.txt");
+        let download_args = ["download", "file-1", "-o", path_str(&recorded_download)];
+        let download = LocalFiles { input: None, output: Some(&recorded_download) };
+        recorder.record(&interaction(&download_args, "", Some("log")), download).unwrap();
+        drop(recorder);
+        assert!(!fs::read_to_string(&cassette).unwrap().contains("recorded"));
+
+        let player = Player::load(&cassette).unwrap();
+        let replayed_inputs = dir.path.join("inputs_b");
+        fs::write(&replayed_inputs, "{\"a\": 1}").unwrap();
+        let inputs = LocalFiles { input: Some(&replayed_inputs), output: None };
+        let replayed_run = args(&["run", "app", "-f", path_str(&replayed_inputs)]);
+        assert_eq!(player.play(&replayed_run, inputs).unwrap().stdout, "job-1");
+        fs::write(&replayed_inputs, "{\"a\": 2}").unwrap();
+        assert!(player.play(&replayed_run, inputs).is_err());
+        let replayed_download = dir.path.join("log.txt");
+        let download = LocalFiles { input: None, output: Some(&replayed_download) };
+        let replayed_download_args =
+            args(&["download", "file-1", "-o", path_str(&replayed_download)]);
+        player.play(&replayed_download_args, download).unwrap();
+        assert_eq!(fs::read_to_string(&replayed_download).unwrap(), "log");
+    }
+
+    #[test]
+    fn replay_steps_through_repeated_calls_and_repeats_the_last() {
+        let dir = TempDir::new("cassette_repeats");
+        let cassette = dir.path.join("cassette.jsonl");
+        let recorder = Recorder::create(&cassette).unwrap();
+        let describe = ["describe", "job-1", "--json"];
+        for state in ["runnable", "running", "done"] {
+            recorder.record(&interaction(&describe, state, None), LocalFiles::default()).unwrap();
+        }
+        drop(recorder);
+        let player = Player::load(&cassette).unwrap();
+        let states: Vec<String> =
+            (0..4).map(|_| player.play(&args(&describe), LocalFiles::default()).unwrap().stdout)
+                .collect();
+        assert_eq!(states, vec!["runnable", "running", "done", "done"]);
+        assert!(player.play(&args(&["describe", "job-2"]), LocalFiles::default()).is_err());
+    }
+
+    #[test]
+    fn replay_ignores_the_created_after_bound_of_job_queries() {
+        let dir = TempDir::new("cassette_created_after");
+        let cassette = dir.path.join("cassette.jsonl");
+        let recorder = Recorder::create(&cassette).unwrap();
+        let find_jobs = |created_after: i64| {
+            let input = format!(r#"{{"project":"project-1","created":{{"after":{}}}}}"#,
+                                created_after);
+            args(&["api", "system", "findJobs", &input])
+        };
+        let recorded =
+            Interaction { args: find_jobs(1700000000000), ..interaction(&[], "[]", None) };
+        recorder.record(&recorded, LocalFiles::default()).unwrap();
+        drop(recorder);
+        let player = Player::load(&cassette).unwrap();
+        let replayed = player.play(&find_jobs(1800000000000), LocalFiles::default()).unwrap();
+        assert_eq!(replayed.stdout, "[]");
+    }
+}
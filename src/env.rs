@@ -7,4 +7,12 @@ mod key {
 
 pub(crate) fn get_home() -> Result<String, Error> {
     Ok(env::var(key::HOME)?)
+}
+
+pub(crate) fn get_opt(key: &str) -> Option<String> {
+    env::var(key).ok()
+}
+
+pub(crate) fn get_all() -> impl Iterator<Item=(String, String)> {
+    env::vars()
 }
\ No newline at end of file
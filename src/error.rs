@@ -6,7 +6,12 @@ use std::string::FromUtf8Error;
 use std::time::SystemTimeError;
 
 #[derive(Copy, Clone, Debug)]
-pub enum ErrorKind { Udix, VarError, Io, Toml, Utf8, ParseInt, SystemTime, SerdeJson }
+pub enum ErrorKind {
+    Udix, VarError, Io, Toml, Utf8, ParseInt, SystemTime, SerdeJson, Dx(DxErrorKind), Unfinished
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DxErrorKind { AuthExpired, NotFound, PermissionDenied, RateLimited, Network, Other }
 
 #[derive(Clone)]
 pub struct Error {
@@ -19,6 +24,7 @@ impl Error {
     pub fn new(kind: ErrorKind, message: String, source: Option<Box<Error>>) -> Error {
         Error { kind, message, source }
     }
+    pub fn kind(&self) -> ErrorKind { self.kind }
     pub fn report(&self) -> String {
         match &self.source {
             None => { format!("{} ({})", self.message, self.kind) }
@@ -89,6 +95,12 @@ impl From<serde_json::Error> for Error {
     }
 }
 
+impl DxErrorKind {
+    pub fn is_transient(&self) -> bool {
+        matches!(self, DxErrorKind::RateLimited | DxErrorKind::Network)
+    }
+}
+
 fn from_error(kind: ErrorKind, error: &dyn std::error::Error) -> Error {
     let message = error.to_string();
     let source: Option<Box<Error>> = None;
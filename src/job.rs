@@ -1,64 +1,240 @@
 use std::fmt::{Display, Formatter};
 use serde::Deserialize;
+use crate::conf::Conf;
 use crate::error::Error;
 
-#[derive(Deserialize, PartialEq)]
-#[serde(try_from = "&str")]
+#[derive(Deserialize, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+#[serde(try_from = "String")]
 pub(crate) enum JobState {
-    Restartable,
+    Idle,
+    WaitingOnInput,
     Runnable,
     Running,
-    Failed,
+    WaitingOnOutput,
+    DebugHold,
+    Restartable,
+    Terminating,
     Done,
+    Failed,
+    Terminated,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub(crate) enum JobStateGroup {
+    Active,
+    Success,
+    Failure,
 }
 
 mod names {
-    pub(crate) const RESTARTABLE: &str = "restartable";
+    pub(crate) const IDLE: &str = "idle";
+    pub(crate) const WAITING_ON_INPUT: &str = "waiting_on_input";
     pub(crate) const RUNNABLE: &str = "runnable";
     pub(crate) const RUNNING: &str = "running";
-    pub(crate) const FAILED: &str = "failed";
+    pub(crate) const WAITING_ON_OUTPUT: &str = "waiting_on_output";
+    pub(crate) const DEBUG_HOLD: &str = "debug_hold";
+    pub(crate) const RESTARTABLE: &str = "restartable";
+    pub(crate) const TERMINATING: &str = "terminating";
     pub(crate) const DONE: &str = "done";
+    pub(crate) const FAILED: &str = "failed";
+    pub(crate) const TERMINATED: &str = "terminated";
 }
 
 impl JobState {
-    pub(crate) fn needs_to_be_submitted(&self) -> bool {
-        *self == JobState::Failed
+    pub(crate) const ALL: [JobState; 11] =
+        [JobState::Idle, JobState::WaitingOnInput, JobState::Runnable, JobState::Running,
+            JobState::WaitingOnOutput, JobState::DebugHold, JobState::Restartable,
+            JobState::Terminating, JobState::Done, JobState::Failed, JobState::Terminated];
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            JobState::Idle => { names::IDLE }
+            JobState::WaitingOnInput => { names::WAITING_ON_INPUT }
+            JobState::Runnable => { names::RUNNABLE }
+            JobState::Running => { names::RUNNING }
+            JobState::WaitingOnOutput => { names::WAITING_ON_OUTPUT }
+            JobState::DebugHold => { names::DEBUG_HOLD }
+            JobState::Restartable => { names::RESTARTABLE }
+            JobState::Terminating => { names::TERMINATING }
+            JobState::Done => { names::DONE }
+            JobState::Failed => { names::FAILED }
+            JobState::Terminated => { names::TERMINATED }
+        }
     }
+    pub(crate) fn group(&self) -> JobStateGroup {
+        match self {
+            JobState::Done => { JobStateGroup::Success }
+            JobState::Failed | JobState::Terminated => { JobStateGroup::Failure }
+            _ => { JobStateGroup::Active }
+        }
+    }
+    pub(crate) fn is_active(&self) -> bool { self.group() == JobStateGroup::Active }
+    pub(crate) fn is_live(&self) -> bool { self.is_active() && *self != JobState::Terminating }
 }
 
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
+pub(crate) enum FailureCause {
+    OutOfMemory,
+    DiskFull,
+    InputMissing,
+    AppError,
+    Other,
+}
+
+pub(crate) struct ResubmitPolicy {
+    states: Vec<JobState>,
+    max_attempts: usize,
+}
+
+impl ResubmitPolicy {
+    pub(crate) fn from_conf(conf: &Conf) -> Result<ResubmitPolicy, Error> {
+        let states =
+            conf.jobs.resubmit_states.iter().map(|state| {
+                JobState::try_from(state.as_str())
+            }).collect::<Result<Vec<JobState>, Error>>()?;
+        let max_attempts = conf.jobs.max_attempts;
+        Ok(ResubmitPolicy { states, max_attempts })
+    }
+    pub(crate) fn needs_to_be_submitted(&self, state: JobState) -> bool {
+        self.states.contains(&state)
+    }
+    pub(crate) fn max_attempts(&self) -> usize { self.max_attempts }
+    pub(crate) fn allows_attempt(&self, n_attempts: usize) -> bool {
+        n_attempts < self.max_attempts
+    }
+}
+
+impl FailureCause {
+    pub(crate) fn classify(reason: Option<&str>, message: Option<&str>) -> FailureCause {
+        let message = message.unwrap_or("").to_lowercase();
+        let mentions = |patterns: &[&str]| { patterns.iter().any(|p| message.contains(p)) };
+        let mentions_word = |words: &[&str]| {
+            message.split(|c: char| !c.is_ascii_alphanumeric()).any(|word| words.contains(&word))
+        };
+        if mentions(&["out of memory", "outofmemory", "cannot allocate memory"])
+            || mentions_word(&["oom", "oomkilled"]) {
+            FailureCause::OutOfMemory
+        } else if mentions(&["no space left", "disk full", "insufficient disk", "disk quota"]) {
+            FailureCause::DiskFull
+        } else {
+            match reason {
+                Some("InputError") | Some("ResourceNotFound") => { FailureCause::InputMissing }
+                Some("AppError") | Some("AppInternalError") => { FailureCause::AppError }
+                _ if mentions(&["not found", "missing input"]) => { FailureCause::InputMissing }
+                _ => { FailureCause::Other }
+            }
+        }
+    }
+    pub(crate) fn needs_larger_instance(&self) -> bool {
+        matches!(self, FailureCause::OutOfMemory | FailureCause::DiskFull)
+    }
+}
 
 impl TryFrom<&str> for JobState {
     type Error = Error;
 
     fn try_from(string: &str) -> Result<Self, Self::Error> {
-        match string {
-            names::RESTARTABLE => { Ok(JobState::Restartable) }
-            names::RUNNABLE => { Ok(JobState::Runnable) }
-            names::RUNNING => { Ok(JobState::Running) }
-            names::FAILED => { Ok(JobState::Failed) }
-            names::DONE => { Ok(JobState::Done) }
-            unknown_state => {
-                Err(Error::from(
-                    format!("Unknown job state {}. Known job states are {}, {}, {} and {}",
-                            unknown_state, names::RUNNABLE, names::RUNNING, names::FAILED,
-                            names::DONE)
-                ))
-            }
-        }
+        JobState::ALL.iter().find(|state| { state.name() == string }).copied().ok_or_else(|| {
+            let known: Vec<&str> = JobState::ALL.iter().map(|state| state.name()).collect();
+            Error::from(format!("Unknown job state {}. Known job states are {}.", string,
+                                known.join(", ")))
+        })
+    }
+}
+
+impl TryFrom<String> for JobState {
+    type Error = Error;
+
+    fn try_from(string: String) -> Result<Self, Self::Error> {
+        JobState::try_from(string.as_str())
     }
 }
 
 impl Display for JobState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl Display for FailureCause {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FailureCause::OutOfMemory => { write!(f, "OOM") }
+            FailureCause::DiskFull => { write!(f, "disk full") }
+            FailureCause::InputMissing => { write!(f, "input missing") }
+            FailureCause::AppError => { write!(f, "AppError") }
+            FailureCause::Other => { write!(f, "other") }
+        }
+    }
+}
+
+impl Display for JobStateGroup {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
-            JobState::Restartable => { write!(f, "{}", names::RESTARTABLE) }
-            JobState::Runnable => { write!(f, "{}", names::RUNNABLE) }
-            JobState::Running => { write!(f, "{}", names::RUNNING) }
-            JobState::Failed => { write!(f, "{}", names::FAILED) }
-            JobState::Done => { write!(f, "{}", names::DONE) }
+            JobStateGroup::Active => { write!(f, "active") }
+            JobStateGroup::Success => { write!(f, "succeeded") }
+            JobStateGroup::Failure => { write!(f, "failed") }
         }
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use crate::testing::cassette_conf;
+    use super::{FailureCause, JobState, ResubmitPolicy};
 
+    fn classify(reason: Option<&str>, message: &str) -> FailureCause {
+        FailureCause::classify(reason, Some(message))
+    }
+
+    #[test]
+    fn classify_out_of_memory() {
+        for message in ["Out of memory", "java.lang.OutOfMemoryError", "OOM killed",
+            "Process was oom-killed", "Container OOMKilled", "cannot allocate memory"] {
+            assert_eq!(classify(Some("AppError"), message), FailureCause::OutOfMemory, "{}",
+                       message);
+        }
+    }
 
+    #[test]
+    fn words_containing_oom_are_not_out_of_memory() {
+        for message in ["No room for output", "zoom level invalid", "bloom filter failed"] {
+            assert_eq!(classify(Some("AppError"), message), FailureCause::AppError, "{}",
+                       message);
+        }
+        assert_eq!(classify(None, "No room left: no space left on device"),
+                   FailureCause::DiskFull);
+    }
+
+    #[test]
+    fn classify_by_reason_and_message() {
+        assert_eq!(classify(Some("AppError"), "No space left on device"), FailureCause::DiskFull);
+        assert_eq!(classify(Some("InputError"), "bad input"), FailureCause::InputMissing);
+        assert_eq!(classify(Some("AppInternalError"), "crash"), FailureCause::AppError);
+        assert_eq!(classify(None, "File not found"), FailureCause::InputMissing);
+        assert_eq!(FailureCause::classify(Some("Terminated"), None), FailureCause::Other);
+    }
+
+    #[test]
+    fn only_resource_failures_need_larger_instances() {
+        assert!(FailureCause::OutOfMemory.needs_larger_instance());
+        assert!(FailureCause::DiskFull.needs_larger_instance());
+        assert!(!FailureCause::AppError.needs_larger_instance());
+    }
+
+    #[test]
+    fn restartable_jobs_are_resubmitted_by_default() {
+        let conf = cassette_conf("/udix/beds/", Path::new("/nonexistent"));
+        let policy = ResubmitPolicy::from_conf(&conf).unwrap();
+        assert!(policy.needs_to_be_submitted(JobState::Restartable));
+        assert!(policy.needs_to_be_submitted(JobState::Failed));
+        assert!(!policy.needs_to_be_submitted(JobState::Terminated));
+    }
+
+    #[test]
+    fn terminating_jobs_are_active_but_not_live() {
+        assert!(JobState::Terminating.is_active());
+        assert!(!JobState::Terminating.is_live());
+        assert!(JobState::Restartable.is_live());
+    }
+}
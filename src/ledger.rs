@@ -0,0 +1,180 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use serde::{Deserialize, Serialize};
+use crate::apps::spec::AppSpec;
+use crate::conf::Conf;
+use crate::error::Error;
+use crate::monitor;
+use crate::monitor::JobInfo;
+use crate::platform::Platform;
+
+mod names {
+    pub(crate) const LEDGER_FILE: &str = "job_ledger.jsonl";
+}
+
+const UDIX_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Attempt {
+    pub(crate) job_id: String,
+    pub(crate) name: String,
+    pub(crate) app: String,
+    #[serde(default)]
+    pub(crate) inputs_md5: Option<String>,
+    pub(crate) instance_type: String,
+    pub(crate) submitted: u64,
+    #[serde(default)]
+    pub(crate) udix_version: String,
+}
+
+impl Attempt {
+    pub(crate) fn new(job_id: String, name: String, app: &str, instance_type: String,
+                      inputs_file: &Path) -> Result<Attempt, Error> {
+        let inputs_md5 = Some(format!("{:x}", md5::compute(fs::read(inputs_file)?)));
+        let submitted = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)?.as_secs();
+        let udix_version = UDIX_VERSION.to_string();
+        Ok(Attempt {
+            job_id, name, app: app.to_string(), inputs_md5, instance_type, submitted, udix_version,
+        })
+    }
+    fn adopted(job: &JobInfo, app: &str) -> Attempt {
+        Attempt {
+            job_id: job.id.clone(),
+            name: job.name.clone(),
+            app: app.to_string(),
+            inputs_md5: None,
+            instance_type: job.instance_type.clone().unwrap_or_default(),
+            submitted: (job.created / 1000) as u64,
+            udix_version: UDIX_VERSION.to_string(),
+        }
+    }
+}
+
+fn ledger_file(conf: &Conf) -> Result<PathBuf, Error> {
+    Ok(PathBuf::from(conf.workspace.work_dir_fixed()?).join(names::LEDGER_FILE))
+}
+
+pub(crate) fn record(conf: &Conf, attempt: &Attempt) -> Result<(), Error> {
+    let file = ledger_file(conf)?;
+    if let Some(parent) = file.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut writer = OpenOptions::new().create(true).append(true).open(file)?;
+    writeln!(writer, "{}", serde_json::to_string(attempt)?)?;
+    Ok(())
+}
+
+pub(crate) fn read_attempts(conf: &Conf) -> Result<Vec<Attempt>, Error> {
+    let file = ledger_file(conf)?;
+    if !file.exists() {
+        return Ok(Vec::new());
+    }
+    let mut attempts: Vec<Attempt> = Vec::new();
+    for line in fs::read_to_string(file)?.lines().filter(|line| !line.trim().is_empty()) {
+        attempts.push(serde_json::from_str(line)?)
+    }
+    Ok(attempts)
+}
+
+pub(crate) fn attempts_by_name(conf: &Conf, app: &str)
+                               -> Result<HashMap<String, Vec<Attempt>>, Error> {
+    let mut attempts_by_name: HashMap<String, Vec<Attempt>> = HashMap::new();
+    for attempt in read_attempts(conf)?.into_iter().filter(|attempt| attempt.app == app) {
+        attempts_by_name.entry(attempt.name.clone()).or_default().push(attempt);
+    }
+    for attempts in attempts_by_name.values_mut() {
+        attempts.sort_by_key(|attempt| attempt.submitted);
+    }
+    Ok(attempts_by_name)
+}
+
+pub(crate) struct LedgeredIds {
+    pub(crate) job_ids: HashSet<String>,
+    pub(crate) oldest_submitted: Option<u64>,
+}
+
+pub(crate) fn ledgered_ids(conf: &Conf, app: &str) -> Result<LedgeredIds, Error> {
+    let attempts: Vec<Attempt> =
+        read_attempts(conf)?.into_iter().filter(|attempt| attempt.app == app).collect();
+    let oldest_submitted = attempts.iter().map(|attempt| attempt.submitted).min();
+    let job_ids = attempts.into_iter().map(|attempt| attempt.job_id).collect();
+    Ok(LedgeredIds { job_ids, oldest_submitted })
+}
+
+pub(crate) fn reconcile(conf: &Conf, platform: &dyn Platform, app: &AppSpec, dry: bool)
+                        -> Result<(), Error> {
+    let mut app_jobs = monitor::find_app_jobs(conf, platform, app)?;
+    app_jobs.unledgered.sort_by(|job1, job2| {
+        job1.created.cmp(&job2.created).then_with(|| job1.id.cmp(&job2.id))
+    });
+    for job in &app_jobs.unledgered {
+        if dry {
+            println!("This would adopt {} ({}), which is {}", job.name, job.id, job.state)
+        } else {
+            record(conf, &Attempt::adopted(job, &app.name))?;
+            println!("Adopted {} ({}), which is {}.", job.name, job.id, job.state)
+        }
+    }
+    let platform_ids: HashSet<&str> =
+        app_jobs.ledgered.iter().chain(app_jobs.unledgered.iter())
+            .map(|job| job.id.as_str()).collect();
+    let mut n_missing: usize = 0;
+    for attempt in read_attempts(conf)?.iter().filter(|attempt| attempt.app == app.name) {
+        if !platform_ids.contains(attempt.job_id.as_str()) {
+            println!("Job {} ({}) from the ledger was not found on the platform.", attempt.name,
+                     attempt.job_id);
+            n_missing += 1;
+        }
+    }
+    println!("{} jobs on the platform and not in the ledger, {} jobs in the ledger and not on \
+        the platform.", app_jobs.unledgered.len(), n_missing);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::time::{SystemTime, UNIX_EPOCH};
+    use serde_json::json;
+    use crate::apps::spec::find_spec;
+    use crate::platform::fake::FakePlatform;
+    use crate::testing::{cassette_conf, TempDir};
+    use super::{attempts_by_name, read_attempts, reconcile, record, Attempt};
+
+    #[test]
+    fn reconcile_adopts_oldest_first_and_latest_attempt_is_last() {
+        let root = TempDir::new("reconcile");
+        let conf = cassette_conf("/udix/beds/", &root.path.join("work"));
+        let app = find_spec(&conf, "vcfs2bed").unwrap();
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as i64;
+        let project = "project-GbZ0Qz8JK4Fq3v6bXyk21vPx";
+        let jobs = json!([
+            {"id": "job-3", "name": "vcfs2bed_c1_b0", "state": "running", "created": now,
+                "project": project},
+            {"id": "job-2", "name": "vcfs2bed_c1_b0", "state": "failed",
+                "created": now - 60_000, "project": project},
+            {"id": "job-1", "name": "vcfs2bed_c1_b0", "state": "failed",
+                "created": now - 120_000, "project": project},
+        ]);
+        fs::create_dir_all(&root.path).unwrap();
+        fs::write(root.path.join("jobs.json"), jobs.to_string()).unwrap();
+        let platform = FakePlatform::new(root.path.clone());
+        let ledgered: Attempt = serde_json::from_value(json!({
+            "job_id": "job-3", "name": "vcfs2bed_c1_b0", "app": "vcfs2bed",
+            "instance_type": "mem1_ssd1_v2_x4", "submitted": now / 1000
+        })).unwrap();
+        record(&conf, &ledgered).unwrap();
+        reconcile(&conf, &platform, &app, false).unwrap();
+        let ids: Vec<String> =
+            read_attempts(&conf).unwrap().into_iter().map(|attempt| attempt.job_id).collect();
+        assert_eq!(ids, vec!["job-3", "job-1", "job-2"]);
+        let attempts = attempts_by_name(&conf, "vcfs2bed").unwrap();
+        let ids: Vec<&str> =
+            attempts["vcfs2bed_c1_b0"].iter().map(|attempt| attempt.job_id.as_str()).collect();
+        assert_eq!(ids, vec!["job-1", "job-2", "job-3"]);
+    }
+}
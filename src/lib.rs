@@ -1,6 +1,8 @@
-use crate::apps::bed_merge::{AppBedMerge, JobBedMerge};
-use crate::apps::vcfs2bed::{AppVcfs2Bed, JobVcfs2Bed};
-use crate::selection::{Choice, Config, Selection, DataChoice, AppChoice, DataSet};
+use crate::apps::spec::AppSpec;
+use crate::conf::Conf;
+use crate::platform::Platform;
+use crate::selection::{Choice, Config, Selection, DataChoice, AppChoice, DataSet, CacheChoice,
+                       Format};
 use crate::error::Error;
 
 pub mod error;
@@ -12,49 +14,114 @@ mod apps;
 mod job;
 mod monitor;
 mod data;
+mod cache;
+mod date;
+mod platform;
+mod retry;
+mod validation;
+mod ledger;
+mod costs;
+mod report;
+mod pipeline;
+mod verify;
+#[cfg(test)]
+mod testing;
 
 pub fn run(selection: Selection) -> Result<(), Error> {
-    let conf = conf::read_conf()?;
+    let platform = platform::get_platform(&selection.params.platform)?;
+    let platform = platform.as_ref();
+    let params = &selection.params;
     match selection.choice {
+        Choice::Config(config_selection) => {
+            match config_selection {
+                Config::Download => { conf::force_download_conf(platform, params)?; }
+                Config::Show { origin } => { conf::show_conf(platform, params, origin)?; }
+                Config::Validate => {
+                    let conf = conf::read_conf(platform, params)?;
+                    validation::check_all(&conf, platform)?;
+                    println!("Config is valid.");
+                }
+                Config::Upload { file } => {
+                    conf::upload_conf(platform, params, file.as_deref())?;
+                }
+                Config::Diff => { conf::diff_conf(platform, params)?; }
+            }
+        }
+        choice => {
+            let conf = conf::read_conf(platform, params)?;
+            run_with_conf(choice, &conf, platform, params.format)?;
+        }
+    }
+    Ok(())
+}
+
+fn supports_format(choice: &Choice) -> bool {
+    match choice {
+        Choice::Data { .. } => { true }
+        Choice::App { app_choice, .. } => {
+            matches!(app_choice, AppChoice::Monitor(_) | AppChoice::Failures | AppChoice::Costs
+                | AppChoice::Verify)
+        }
+        _ => { false }
+    }
+}
+
+fn run_with_conf(choice: Choice, conf: &Conf, platform: &dyn Platform, format: Format)
+                 -> Result<(), Error> {
+    if format != Format::Text && !supports_format(&choice) {
+        Err(Error::from("Only listing, survey, monitor, failures, costs and verify commands \
+            support formats other than text."))?
+    }
+    match choice {
         Choice::Data {data_set, data_choice } => {
             match data_set {
                 DataSet::Vcfs => {
                     match data_choice {
-                        DataChoice::List => { data::vcfs::list_vcfs(&conf)?; }
-                        DataChoice::Survey => { data::vcfs::survey_vcfs(&conf)? }
+                        DataChoice::List => { data::vcfs::list_vcfs(conf, platform, format)?; }
+                        DataChoice::Survey => { data::vcfs::survey_vcfs(conf, platform, format)? }
                     }
                 }
                 DataSet::Beds => {
                     match data_choice {
-                        DataChoice::List => { data::beds::list_beds(&conf)?; }
-                        DataChoice::Survey => { data::beds::survey_beds(&conf)? }
+                        DataChoice::List => { data::beds::list_beds(conf, platform, format)?; }
+                        DataChoice::Survey => { data::beds::survey_beds(conf, platform, format)? }
                     }
                 }
             }
         }
-        Choice::Vcfs2Bed(vcfs2bed_selection) => {
-            match vcfs2bed_selection {
-                AppChoice::Run(run_choice) => {
-                    apps::run_jobs::<AppVcfs2Bed>(&conf, &run_choice)?;
-                }
-                AppChoice::Monitor => { apps::monitor_jobs::<JobVcfs2Bed>(&conf)?; }
-            }
+        Choice::App { app, app_choice } => {
+            let app = apps::spec::find_spec(conf, &app)?;
+            run_app_choice(app_choice, conf, platform, &app, format)?;
         }
-        Choice::BedMerge(bed_merge_selection) => {
-            match bed_merge_selection {
-                AppChoice::Run(run_choice) => {
-                    apps::run_jobs::<AppBedMerge>(&conf, &run_choice)?;
-                }
-                AppChoice::Monitor => { apps::monitor_jobs::<JobBedMerge>(&conf)?; }
-            }
+        Choice::Pipeline(run_choice) => {
+            pipeline::run_pipeline(conf, platform, &run_choice)?;
         }
-        Choice::Config(config_selection) => {
-            match config_selection {
-                Config::Download => {
-                    todo!()
-                }
+        Choice::Config(_) => { unreachable!("Config commands run before reading the config.") }
+        Choice::Cache(cache_choice) => {
+            match cache_choice {
+                CacheChoice::Show => { cache::show_cache(conf)?; }
+                CacheChoice::Clear => { cache::clear_cache(conf)?; }
             }
         }
     }
     Ok(())
-}
\ No newline at end of file
+}
+
+fn run_app_choice(app_choice: AppChoice, conf: &Conf, platform: &dyn Platform, app: &AppSpec,
+                  format: Format) -> Result<(), Error> {
+    match app_choice {
+        AppChoice::Run(run_choice) => { apps::run_jobs(conf, platform, app, &run_choice)?; }
+        AppChoice::Monitor(monitor_choice) => {
+            apps::monitor_jobs(conf, platform, app, &monitor_choice, format)?;
+        }
+        AppChoice::Logs { job_name } => { apps::show_log(conf, platform, app, &job_name)?; }
+        AppChoice::Failures => { monitor::report_failures(conf, platform, app, format)?; }
+        AppChoice::Costs => { costs::report_costs(conf, platform, app, format)?; }
+        AppChoice::Terminate(terminate_choice) => {
+            apps::terminate_jobs(conf, platform, app, &terminate_choice)?;
+        }
+        AppChoice::Reconcile { dry } => { ledger::reconcile(conf, platform, app, dry)?; }
+        AppChoice::Verify => { verify::verify_outputs(conf, platform, app, format)?; }
+    }
+    Ok(())
+}
@@ -2,14 +2,17 @@ mod cli;
 
 use udix::run;
 use std::process::exit;
-use udix::error::Error;
+use udix::error::{Error, ErrorKind};
 
 fn main() {
     match do_run() {
         Ok(_) => {}
         Err(error) => {
             eprintln!("Error: {error}");
-            exit(1)
+            match error.kind() {
+                ErrorKind::Unfinished => { exit(2) }
+                _ => { exit(1) }
+            }
         }
     }
 }
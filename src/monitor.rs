@@ -1,31 +1,533 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::IsTerminal;
+use std::process::Command;
+use std::thread::sleep;
+use std::time::Duration;
+use crate::apps::spec::{AppSpec, StagedJob};
 use crate::conf::Conf;
-use crate::dx;
-use crate::error::Error;
-use serde::Deserialize;
-use crate::job::JobState;
+use crate::data::chromosome::Chromosome;
+use crate::date::parse_date_millis;
+use crate::error::{Error, ErrorKind};
+use serde::{Deserialize, Serialize};
+use crate::job::{FailureCause, JobState, JobStateGroup};
+use crate::ledger;
+use crate::platform::{JobQuery, OnJob, Platform};
+use crate::report::print_rows;
+use crate::selection::Format;
 
 #[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub(crate) struct JobInfo {
+    pub(crate) id: String,
     pub(crate) name: String,
-    pub(crate) state: JobState
+    pub(crate) state: JobState,
+    #[serde(default)]
+    pub(crate) created: i64,
+    pub(crate) instance_type: Option<String>,
+    pub(crate) started_running: Option<i64>,
+    pub(crate) stopped_running: Option<i64>,
+    pub(crate) total_price: Option<f64>,
+    pub(crate) launched_by: Option<String>,
+    pub(crate) failure_reason: Option<String>,
+    pub(crate) failure_message: Option<String>,
 }
 
-pub(crate) fn find_jobs(conf: &Conf) -> Result<Vec<JobInfo>, Error> {
-    let start_date = conf.misc.start_date.as_str();
-    let output =
-        dx::capture_stdout(&["find", "jobs", "-n", "100000", "--created-after", start_date,
-            "--json"])?;
-    let jobs: Vec<JobInfo> = serde_json::from_str(&output)?;
-    Ok(jobs)
+impl JobInfo {
+    pub(crate) fn failure_cause(&self) -> Option<FailureCause> {
+        if self.state.group() == JobStateGroup::Failure {
+            Some(FailureCause::classify(self.failure_reason.as_deref(),
+                                        self.failure_message.as_deref()))
+        } else {
+            None
+        }
+    }
+}
+
+const CLOCK_SKEW_MILLIS: i64 = 24 * 3600 * 1000;
+
+pub(crate) fn for_each_job(conf: &Conf, platform: &dyn Platform, name_prefix: &str,
+                           created_after: i64, on_job: &mut OnJob) -> Result<(), Error> {
+    let project = conf.project(platform)?;
+    let query = JobQuery {
+        created_after,
+        project: project.as_str(),
+        name_prefix: Some(name_prefix),
+        launched_by: conf.workspace.launched_by.as_deref(),
+    };
+    platform.find_jobs(&query, on_job)
+}
+
+pub(crate) struct AppJobs {
+    pub(crate) ledgered: Vec<JobInfo>,
+    pub(crate) unledgered: Vec<JobInfo>,
+}
+
+pub(crate) fn find_app_jobs(conf: &Conf, platform: &dyn Platform, app: &AppSpec)
+                            -> Result<AppJobs, Error> {
+    let ledgered_ids = ledger::ledgered_ids(conf, &app.name)?;
+    let start_date_millis = parse_date_millis(&conf.misc.start_date)?;
+    let created_after =
+        match ledgered_ids.oldest_submitted {
+            None => { start_date_millis }
+            Some(submitted) => {
+                start_date_millis.min(submitted as i64 * 1000 - CLOCK_SKEW_MILLIS)
+            }
+        };
+    let mut ledgered: Vec<JobInfo> = Vec::new();
+    let mut unledgered: Vec<JobInfo> = Vec::new();
+    for_each_job(conf, platform, &app.prefix, created_after, &mut |job| {
+        if ledgered_ids.job_ids.contains(&job.id) {
+            ledgered.push(job)
+        } else if job.created >= start_date_millis && app.is_name(&job.name) {
+            unledgered.push(job)
+        }
+        Ok(())
+    })?;
+    Ok(AppJobs { ledgered, unledgered })
+}
+
+pub(crate) struct JobHistory {
+    pub(crate) latest: JobInfo,
+    pub(crate) earlier: Vec<JobInfo>,
+}
+
+impl JobHistory {
+    fn add(&mut self, job: JobInfo) {
+        if job.created > self.latest.created {
+            let previous = std::mem::replace(&mut self.latest, job);
+            self.earlier.push(previous)
+        } else {
+            self.earlier.push(job)
+        }
+    }
+    pub(crate) fn jobs(&self) -> impl Iterator<Item=&JobInfo> {
+        self.earlier.iter().chain(std::iter::once(&self.latest))
+    }
+    pub(crate) fn n_live(&self) -> usize {
+        self.jobs().filter(|job| job.state.is_live()).count()
+    }
+}
+
+pub(crate) fn by_name(jobs: Vec<JobInfo>) -> HashMap<String, JobHistory> {
+    let mut jobs_by_name: HashMap<String, JobHistory> = HashMap::new();
+    for job in jobs {
+        match jobs_by_name.get_mut(&job.name) {
+            None => {
+                let history = JobHistory { latest: job, earlier: Vec::new() };
+                jobs_by_name.insert(history.latest.name.clone(), history);
+            }
+            Some(history) => { history.add(job) }
+        }
+    }
+    for history in jobs_by_name.values_mut() {
+        history.earlier.sort_by_key(|job| job.created);
+    }
+    jobs_by_name
+}
+
+pub(crate) fn jobs_by_name(conf: &Conf, platform: &dyn Platform, app: &AppSpec)
+                           -> Result<HashMap<String, JobHistory>, Error> {
+    Ok(by_name(find_app_jobs(conf, platform, app)?.ledgered))
+}
+
+#[derive(Serialize, Default)]
+struct FailureRow {
+    cause: String,
+    name: String,
+    job_id: String,
+    failure_reason: Option<String>,
+    failure_message: Option<String>,
 }
 
-pub(crate) fn jobs_by_name(conf: &Conf) -> Result<HashMap<String, JobInfo>, Error> {
-    let mut jobs_by_name: HashMap<String, JobInfo> = HashMap::new();
-    for job in find_jobs(conf)? {
-        if !jobs_by_name.contains_key(&job.name) {
-            jobs_by_name.insert(job.name.clone(), job);
+pub(crate) fn report_failures(conf: &Conf, platform: &dyn Platform, app: &AppSpec,
+                              format: Format) -> Result<(), Error> {
+    let mut failures: BTreeMap<FailureCause, Vec<JobInfo>> = BTreeMap::new();
+    for job in jobs_by_name(conf, platform, app)?.into_values().map(|history| history.latest) {
+        if let Some(cause) = job.failure_cause() {
+            failures.entry(cause).or_default().push(job)
         }
     }
-    Ok(jobs_by_name)
-}
\ No newline at end of file
+    for jobs in failures.values_mut() {
+        jobs.sort_by(|job1, job2| job1.name.cmp(&job2.name));
+    }
+    let rows: Vec<FailureRow> =
+        failures.iter().flat_map(|(cause, jobs)| {
+            jobs.iter().map(|job| FailureRow {
+                cause: cause.to_string(),
+                name: job.name.clone(),
+                job_id: job.id.clone(),
+                failure_reason: job.failure_reason.clone(),
+                failure_message: job.failure_message.clone(),
+            })
+        }).collect();
+    print_rows(format, &rows, || {
+        if failures.is_empty() {
+            println!("No failed jobs.");
+        }
+        for (cause, jobs) in &failures {
+            println!("{} ({}):", cause, jobs.len());
+            for job in jobs {
+                let reason = job.failure_reason.as_deref().unwrap_or("unknown reason");
+                match &job.failure_message {
+                    None => { println!("  {} ({}): {}", job.name, job.id, reason) }
+                    Some(message) => {
+                        println!("  {} ({}): {}: {}", job.name, job.id, reason, message)
+                    }
+                }
+            }
+        }
+    })
+}
+
+pub(crate) struct Summary {
+    counts: BTreeMap<Chromosome, BTreeMap<JobState, usize>>,
+    unsubmitted_counts: BTreeMap<Chromosome, usize>,
+    n_expected: usize,
+    n_done: usize,
+    n_active: usize,
+    n_failed: usize,
+    never_submitted: Vec<String>,
+    never_submitted_blocks: BTreeMap<Chromosome, Vec<usize>>,
+    attempt_history: Vec<String>,
+    duplicates: Vec<String>,
+    unparsed: Vec<String>,
+    statuses: Vec<JobStatus>,
+}
+
+#[derive(Serialize, Default)]
+struct JobStatus {
+    name: String,
+    chromosome: String,
+    block: Option<usize>,
+    state: String,
+    job_id: Option<String>,
+    instance_type: Option<String>,
+    n_attempts: usize,
+}
+
+const UNSUBMITTED: &str = "unsubmitted";
+const DEFAULT_INTERVAL_SECS: u64 = 60;
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+
+impl Summary {
+    pub(crate) fn new(app: &AppSpec, expected: &[StagedJob], jobs: &HashMap<String, JobHistory>)
+                      -> Summary {
+        let mut counts: BTreeMap<Chromosome, BTreeMap<JobState, usize>> = BTreeMap::new();
+        let mut unparsed: Vec<String> = Vec::new();
+        let mut duplicates: Vec<String> = Vec::new();
+        for history in jobs.values() {
+            if history.n_live() > 1 {
+                let ids: Vec<&str> =
+                    history.jobs().filter(|job| job.state.is_live())
+                        .map(|job| job.id.as_str()).collect();
+                duplicates.push(format!("{}: {}", history.latest.name, ids.join(", ")))
+            }
+        }
+        for job in jobs.values().map(|history| &history.latest) {
+            match app.parse_chromosome(&job.name) {
+                None => { unparsed.push(job.name.clone()) }
+                Some(chromosome) => {
+                    *counts.entry(chromosome).or_default().entry(job.state).or_default()
+                        += 1
+                }
+            }
+        }
+        let mut unsubmitted_counts: BTreeMap<Chromosome, usize> = BTreeMap::new();
+        let mut never_submitted: Vec<String> = Vec::new();
+        let mut never_submitted_blocks: BTreeMap<Chromosome, Vec<usize>> = BTreeMap::new();
+        let mut n_done: usize = 0;
+        let mut n_active: usize = 0;
+        let mut n_failed: usize = 0;
+        let mut attempt_history: Vec<String> = Vec::new();
+        let mut statuses: Vec<JobStatus> = Vec::new();
+        for job in expected {
+            let name = job.name.clone();
+            let history = jobs.get(&name);
+            statuses.push(JobStatus {
+                name: name.clone(),
+                chromosome: job.chromosome.to_string(),
+                block: job.block,
+                state: history.map(|history| history.latest.state.to_string())
+                    .unwrap_or(UNSUBMITTED.to_string()),
+                job_id: history.map(|history| history.latest.id.clone()),
+                instance_type: history.and_then(|history| history.latest.instance_type.clone()),
+                n_attempts: history.map(|history| history.jobs().count()).unwrap_or(0),
+            });
+            if let Some(history) = jobs.get(&name).filter(|history| !history.earlier.is_empty()) {
+                let attempts: Vec<String> =
+                    history.jobs().map(|job| {
+                        let instance_type = job.instance_type.as_deref().unwrap_or("unknown");
+                        format!("{} {} on {}", job.id, job.state, instance_type)
+                    }).collect();
+                attempt_history.push(format!("{}: {}", name, attempts.join(", ")))
+            }
+            match jobs.get(&name).map(|history| &history.latest) {
+                None => {
+                    *unsubmitted_counts.entry(job.chromosome).or_default() += 1;
+                    match job.block {
+                        None => { never_submitted.push(name) }
+                        Some(block) => {
+                            never_submitted_blocks.entry(job.chromosome).or_default()
+                                .push(block)
+                        }
+                    }
+                }
+                Some(job_info) => {
+                    match job_info.state.group() {
+                        JobStateGroup::Active => { n_active += 1 }
+                        JobStateGroup::Success => { n_done += 1 }
+                        JobStateGroup::Failure => { n_failed += 1 }
+                    }
+                }
+            }
+        }
+        unparsed.sort();
+        duplicates.sort();
+        Summary {
+            counts, unsubmitted_counts, n_expected: expected.len(), n_done, n_active, n_failed,
+            never_submitted, never_submitted_blocks, attempt_history, duplicates, unparsed,
+            statuses,
+        }
+    }
+    pub(crate) fn print_as(&self, format: Format) -> Result<(), Error> {
+        print_rows(format, &self.statuses, || self.print())
+    }
+    pub(crate) fn is_finished(&self) -> bool { self.n_active == 0 }
+    pub(crate) fn is_complete(&self) -> bool { self.n_done == self.n_expected }
+    pub(crate) fn check_duplicates(&self) -> Result<(), Error> {
+        if self.duplicates.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::from(format!("{} job names have more than one live job.",
+                                    self.duplicates.len())))
+        }
+    }
+    fn progress_line(&self) -> String {
+        let percent_done =
+            if self.n_expected == 0 {
+                100.0
+            } else {
+                100.0 * (self.n_done as f64) / (self.n_expected as f64)
+            };
+        format!("{} of {} expected jobs done ({:.1}%), {} active, {} failed, {} never submitted.",
+                self.n_done, self.n_expected, percent_done, self.n_active, self.n_failed,
+                self.n_never_submitted())
+    }
+    fn n_never_submitted(&self) -> usize {
+        self.never_submitted.len()
+            + self.never_submitted_blocks.values().map(|blocks| blocks.len()).sum::<usize>()
+    }
+    pub(crate) fn print(&self) {
+        println!("{}", self.progress_line());
+        let states: Vec<JobState> =
+            JobState::ALL.iter().filter(|state| {
+                self.counts.values().any(|counts| counts.contains_key(state))
+            }).copied().collect();
+        let mut chromosomes: Vec<Chromosome> =
+            self.counts.keys().chain(self.unsubmitted_counts.keys()).copied().collect();
+        chromosomes.sort();
+        chromosomes.dedup();
+        if chromosomes.is_empty() {
+            return;
+        }
+        let mut header = format!("{:>4}", "chr");
+        for state in &states {
+            header.push_str(&format!(" {}", state.name()));
+        }
+        header.push_str(&format!(" {}", UNSUBMITTED));
+        println!("{}", header);
+        for chromosome in chromosomes {
+            let mut line = format!("{:>4}", chromosome.to_string());
+            let counts = self.counts.get(&chromosome);
+            for state in &states {
+                let count = counts.and_then(|counts| counts.get(state)).copied().unwrap_or(0);
+                line.push_str(&format!(" {:>width$}", count, width = state.name().len()));
+            }
+            let unsubmitted = self.unsubmitted_counts.get(&chromosome).copied().unwrap_or(0);
+            line.push_str(&format!(" {:>width$}", unsubmitted, width = UNSUBMITTED.len()));
+            println!("{}", line);
+        }
+        if self.n_never_submitted() > 0 {
+            println!("Expected but never submitted ({}):", self.n_never_submitted());
+            for name in &self.never_submitted {
+                println!("  {}", name);
+            }
+            for (chromosome, blocks) in &self.never_submitted_blocks {
+                let blocks: Vec<String> = blocks.iter().map(|block| block.to_string()).collect();
+                println!("  chromosome {}, blocks {}", chromosome, blocks.join(", "));
+            }
+        }
+        if !self.attempt_history.is_empty() {
+            println!("Jobs with several attempts ({}):", self.attempt_history.len());
+            for history in &self.attempt_history {
+                println!("  {}", history);
+            }
+        }
+        if !self.duplicates.is_empty() {
+            println!("Error: job names with more than one live job ({}):", self.duplicates.len());
+            for duplicate in &self.duplicates {
+                println!("  {}", duplicate);
+            }
+        }
+        if !self.unparsed.is_empty() {
+            println!("Jobs with names that could not be parsed ({}):", self.unparsed.len());
+            for name in &self.unparsed {
+                println!("  {}", name);
+            }
+        }
+    }
+}
+
+pub(crate) fn watch_jobs(conf: &Conf, platform: &dyn Platform, app: &AppSpec,
+                         expected: &[StagedJob], interval: Option<u64>) -> Result<(), Error> {
+    let app_name = app.name.as_str();
+    let interval_secs = interval.unwrap_or(DEFAULT_INTERVAL_SECS);
+    let redraw = std::io::stdout().is_terminal();
+    let summary =
+        loop {
+            let submitted_jobs = jobs_by_name(conf, platform, app)?;
+            let summary = Summary::new(app, expected, &submitted_jobs);
+            if redraw {
+                print!("{}", CLEAR_SCREEN);
+            }
+            summary.print();
+            summary.check_duplicates()?;
+            if summary.is_finished() {
+                break summary;
+            }
+            println!("Checking again in {} seconds.", interval_secs);
+            sleep(Duration::from_secs(interval_secs));
+        };
+    on_finish(conf, app_name, &summary)?;
+    if summary.is_complete() {
+        Ok(())
+    } else {
+        let message =
+            format!("No {} jobs are active, but only {} of {} expected jobs are done.", app_name,
+                    summary.n_done, summary.n_expected);
+        Err(Error::new(ErrorKind::Unfinished, message, None))
+    }
+}
+
+fn on_finish(conf: &Conf, app_name: &str, summary: &Summary) -> Result<(), Error> {
+    if let Some(finish_marker) = conf.monitor.finish_marker_fixed()? {
+        fs::write(&finish_marker, format!("{}: {}\n", app_name, summary.progress_line()))?;
+        println!("Wrote finish marker {}.", finish_marker);
+    }
+    if let Some(command) = &conf.monitor.on_finish {
+        println!("Running {}", command);
+        let status = Command::new("sh").arg("-c").arg(command).status()?;
+        if !status.success() {
+            Err(Error::from(format!("Command '{}' failed with {}.", command, status)))?
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+    use std::time::{SystemTime, UNIX_EPOCH};
+    use serde_json::json;
+    use crate::apps::spec::find_spec;
+    use crate::ledger::{self, Attempt};
+    use crate::platform::fake::FakePlatform;
+    use crate::testing::{cassette_conf, replay_cassette, TempDir};
+    use super::{by_name, watch_jobs, JobInfo, Summary};
+
+    fn job(id: &str, name: &str, state: &str, created: i64) -> JobInfo {
+        serde_json::from_value(json!({
+            "id": id, "name": name, "state": state, "created": created,
+            "instanceType": "mem2_hdd2_v2_x4"
+        })).unwrap()
+    }
+
+    #[test]
+    fn summarize_recorded_vcf_folder_against_submitted_jobs() {
+        let conf = cassette_conf("/udix/beds/", Path::new("/nonexistent"));
+        let app = find_spec(&conf, "vcfs2bed").unwrap();
+        let platform = replay_cassette("vcf_folder.jsonl");
+        let expected = app.create_job_list_unfiltered(&conf, &platform).unwrap();
+        let jobs = by_name(vec![
+            job("job-1", "vcfs2bed_c1_b0", "done", 1),
+            job("job-2", "vcfs2bed_c1_b1", "running", 2),
+            job("job-3", "vcfs2bed_c2_b0", "failed", 3),
+            job("job-4", "vcfs2bed_other", "done", 4),
+        ]);
+        let summary = Summary::new(&app, &expected, &jobs);
+        assert_eq!(summary.n_expected, 5);
+        assert_eq!((summary.n_done, summary.n_active, summary.n_failed), (1, 1, 1));
+        assert!(!summary.is_finished());
+        assert!(!summary.is_complete());
+        let counts: Vec<(String, String, usize)> =
+            summary.counts.iter().flat_map(|(chromosome, states)| {
+                states.iter().map(move |(state, count)| {
+                    (chromosome.to_string(), state.to_string(), *count)
+                })
+            }).collect();
+        assert_eq!(counts, vec![
+            ("1".to_string(), "running".to_string(), 1),
+            ("1".to_string(), "done".to_string(), 1),
+            ("2".to_string(), "failed".to_string(), 1),
+        ]);
+        let never_submitted_blocks: Vec<(String, Vec<usize>)> =
+            summary.never_submitted_blocks.iter()
+                .map(|(chromosome, blocks)| (chromosome.to_string(), blocks.clone())).collect();
+        assert_eq!(never_submitted_blocks,
+                   vec![("10".to_string(), vec![0]), ("X".to_string(), vec![0])]);
+        assert!(summary.never_submitted.is_empty());
+        assert_eq!(summary.unparsed, vec!["vcfs2bed_other"]);
+        let states: Vec<&str> =
+            summary.statuses.iter().map(|status| status.state.as_str()).collect();
+        assert_eq!(states, vec!["done", "running", "failed", "unsubmitted", "unsubmitted"]);
+    }
+
+    #[test]
+    fn by_name_keeps_the_latest_job_and_flags_duplicate_live_jobs() {
+        let conf = cassette_conf("/udix/beds/", Path::new("/nonexistent"));
+        let app = find_spec(&conf, "vcfs2bed").unwrap();
+        let jobs = by_name(vec![
+            job("job-2", "vcfs2bed_c1_b0", "done", 2),
+            job("job-1", "vcfs2bed_c1_b0", "failed", 1),
+            job("job-3", "vcfs2bed_c1_b0", "failed", 0),
+            job("job-4", "vcfs2bed_c2_b0", "runnable", 4),
+            job("job-5", "vcfs2bed_c2_b0", "idle", 5),
+            job("job-6", "vcfs2bed_c10_b0", "terminating", 6),
+            job("job-7", "vcfs2bed_c10_b0", "runnable", 7),
+        ]);
+        let history = &jobs["vcfs2bed_c1_b0"];
+        assert_eq!(history.latest.id, "job-2");
+        let earlier: Vec<&str> = history.earlier.iter().map(|job| job.id.as_str()).collect();
+        assert_eq!(earlier, vec!["job-3", "job-1"]);
+        let summary = Summary::new(&app, &[], &jobs);
+        assert_eq!(summary.duplicates, vec!["vcfs2bed_c2_b0: job-4, job-5"]);
+        assert!(summary.check_duplicates().is_err());
+    }
+
+    #[test]
+    fn watch_stops_at_duplicate_live_jobs() {
+        let temp_dir = TempDir::new("watch_duplicates");
+        let conf = cassette_conf("/udix/beds/", &temp_dir.path.join("work"));
+        let app = find_spec(&conf, "vcfs2bed").unwrap();
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as i64;
+        let mut jobs: Vec<serde_json::Value> = Vec::new();
+        for id in ["job-1", "job-2"] {
+            jobs.push(json!({
+                "id": id, "name": "vcfs2bed_c1_b0", "state": "running", "created": now,
+                "project": "project-GbZ0Qz8JK4Fq3v6bXyk21vPx", "launchedBy": "user-fake"
+            }));
+            let attempt: Attempt = serde_json::from_value(json!({
+                "job_id": id, "name": "vcfs2bed_c1_b0", "app": "vcfs2bed",
+                "instance_type": "mem1_ssd1_v2_x4", "submitted": now / 1000
+            })).unwrap();
+            ledger::record(&conf, &attempt).unwrap();
+        }
+        fs::create_dir_all(&temp_dir.path).unwrap();
+        fs::write(temp_dir.path.join("jobs.json"), serde_json::to_string(&jobs).unwrap()).unwrap();
+        let platform = FakePlatform::new(temp_dir.path.clone());
+        let error = watch_jobs(&conf, &platform, &app, &[], Some(0)).unwrap_err();
+        assert!(error.to_string().contains("more than one live job"), "{}", error);
+    }
+}
@@ -0,0 +1,164 @@
+use std::collections::{BTreeMap, HashMap};
+use crate::apps::BatchSubmission;
+use crate::apps::spec::{builtin, find_spec, AppSpec, StagedJob};
+use crate::conf::Conf;
+use crate::data::chromosome::Chromosome;
+use crate::error::Error;
+use crate::job::{JobStateGroup, ResubmitPolicy};
+use crate::ledger::Attempt;
+use crate::monitor::JobHistory;
+use crate::platform::Platform;
+use crate::selection::RunChoice;
+use crate::{apps, cache, ledger, monitor, validation};
+
+enum BlockStep {
+    Submit(StagedJob),
+    Reuse(String),
+    Blocked(String),
+}
+
+struct SubmittedJobs {
+    by_name: HashMap<String, JobHistory>,
+    attempts: HashMap<String, Vec<Attempt>>,
+}
+
+impl SubmittedJobs {
+    fn find(conf: &Conf, platform: &dyn Platform, app: &AppSpec)
+            -> Result<SubmittedJobs, Error> {
+        let app_jobs = monitor::find_app_jobs(conf, platform, app)?;
+        apps::check_unledgered(app, &app_jobs)?;
+        let by_name = monitor::by_name(app_jobs.ledgered);
+        let attempts = ledger::attempts_by_name(conf, &app.name)?;
+        Ok(SubmittedJobs { by_name, attempts })
+    }
+    fn should_be_run(&self, name: &str, policy: &ResubmitPolicy) -> bool {
+        apps::should_be_run(name, &self.by_name, &self.attempts, policy)
+    }
+    fn instance_type_for(&self, app: &AppSpec, name: &str, chromosome: Chromosome) -> String {
+        apps::instance_type_for(app, name, chromosome, &self.by_name, &self.attempts)
+    }
+}
+
+fn plan_block(job: StagedJob, block_jobs: &SubmittedJobs, policy: &ResubmitPolicy)
+              -> BlockStep {
+    let name = job.name.clone();
+    match block_jobs.by_name.get(&name).map(|history| &history.latest) {
+        Some(latest) if !block_jobs.should_be_run(&name, policy) => {
+            match latest.state.group() {
+                JobStateGroup::Failure => { BlockStep::Blocked(name) }
+                JobStateGroup::Active | JobStateGroup::Success => {
+                    BlockStep::Reuse(latest.id.clone())
+                }
+            }
+        }
+        _ => { BlockStep::Submit(job) }
+    }
+}
+
+pub(crate) fn run_pipeline(conf: &Conf, platform: &dyn Platform, run: &RunChoice)
+                           -> Result<(), Error> {
+    if run.names.is_some() {
+        Err(Error::from("Pipeline runs do not take a list of job names."))?
+    }
+    let block_app = find_spec(conf, builtin::VCFS2BED)?;
+    let merge_app = find_spec(conf, builtin::BED_MERGE)?;
+    validation::check_for_pipeline(conf, platform, &block_app, &merge_app)?;
+    let policy = ResubmitPolicy::from_conf(conf)?;
+    let block_jobs = SubmittedJobs::find(conf, platform, &block_app)?;
+    let merge_jobs = SubmittedJobs::find(conf, platform, &merge_app)?;
+    let mut blocks_by_chr: BTreeMap<Chromosome, Vec<StagedJob>> = BTreeMap::new();
+    for job in block_app.create_job_list_unfiltered(conf, platform)? {
+        blocks_by_chr.entry(job.chromosome).or_default().push(job)
+    }
+    let mut plans: Vec<(Chromosome, Vec<BlockStep>)> = Vec::new();
+    for (chromosome, blocks) in blocks_by_chr {
+        let merge_name = merge_app.job_name(chromosome, None);
+        if apps::passes_pat(&merge_name, &run.pat)
+            && merge_jobs.should_be_run(&merge_name, &policy) {
+            let steps: Vec<BlockStep> =
+                blocks.into_iter().map(|job| plan_block(job, &block_jobs, &policy)).collect();
+            plans.push((chromosome, steps))
+        }
+    }
+    if let Some(num) = run.num {
+        plans.truncate(num)
+    }
+    let names: Vec<String> =
+        plans.iter().flat_map(|(_, steps)| steps).flat_map(|step| {
+            match step {
+                BlockStep::Submit(job) => { job.input_file_names() }
+                BlockStep::Reuse(_) | BlockStep::Blocked(_) => { Vec::new() }
+            }
+        }).collect();
+    let links =
+        if run.dry || names.is_empty() {
+            None
+        } else {
+            let folder = conf.in_project(block_app.inputs_folder(conf));
+            Some(cache::resolve_links(conf, platform, &folder, &names)?)
+        };
+    let mut batch = BatchSubmission::new();
+    for (chromosome, steps) in plans {
+        let mut block_job_ids: Vec<String> = Vec::new();
+        let mut blocked: Vec<String> = Vec::new();
+        let mut unsubmitted: Vec<String> = Vec::new();
+        for step in steps {
+            match step {
+                BlockStep::Submit(job) => {
+                    let name = job.name.clone();
+                    let instance_type =
+                        block_jobs.instance_type_for(&block_app, &name, chromosome);
+                    match &links {
+                        None => {
+                            apps::print_dry_terminate(&name, &block_jobs.by_name);
+                            println!("This would run {} on {}", name, instance_type);
+                            block_job_ids.push(name)
+                        }
+                        Some(links) => {
+                            let result =
+                                apps::terminate_restartable(platform, &name, &block_jobs.by_name)
+                                    .and_then(|()| {
+                                        apps::run_job(conf, platform, &block_app, &job, links,
+                                                      instance_type)
+                                    });
+                            match batch.add(&name, result)? {
+                                None => { unsubmitted.push(name) }
+                                Some(job_id) => { block_job_ids.push(job_id) }
+                            }
+                        }
+                    }
+                }
+                BlockStep::Reuse(job_id) => { block_job_ids.push(job_id) }
+                BlockStep::Blocked(name) => { blocked.push(name) }
+            }
+        }
+        let merge_name = merge_app.job_name(chromosome, None);
+        if !blocked.is_empty() {
+            println!("Not submitting {}, because {} failed and will not be resubmitted.",
+                     merge_name, blocked.join(", "));
+            continue;
+        }
+        if !unsubmitted.is_empty() {
+            println!("Not submitting {}, because {} could not be submitted.", merge_name,
+                     unsubmitted.join(", "));
+            continue;
+        }
+        let instance_type =
+            merge_jobs.instance_type_for(&merge_app, &merge_name, chromosome);
+        if run.dry {
+            apps::print_dry_terminate(&merge_name, &merge_jobs.by_name);
+            println!("This would run {} on {} once {} block jobs are done", merge_name,
+                     instance_type, block_job_ids.len());
+        } else {
+            let inputs = merge_app.inputs_from_jobs(&merge_name, &block_app, &block_job_ids)?;
+            let result =
+                apps::terminate_restartable(platform, &merge_name, &merge_jobs.by_name)
+                    .and_then(|()| {
+                        apps::submit_job(conf, platform, &merge_app, merge_name.clone(),
+                                         chromosome, &inputs, instance_type)
+                    });
+            batch.add(&merge_name, result)?;
+        }
+    }
+    batch.finish(&[&block_app, &merge_app])
+}
@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use crate::dx;
+use crate::dx::{DxCli, DxMode};
+use crate::error::Error;
+use crate::monitor::JobInfo;
+use crate::platform::api::ApiPlatform;
+use crate::platform::fake::FakePlatform;
+
+pub(crate) mod api;
+pub(crate) mod fake;
+
+mod names {
+    pub(crate) const DX: &str = "dx";
+    pub(crate) const API: &str = "api";
+    pub(crate) const FAKE: &str = "fake";
+    pub(crate) const RECORD: &str = "record";
+    pub(crate) const REPLAY: &str = "replay";
+}
+
+#[derive(Deserialize)]
+pub(crate) struct FileInfo {
+    pub(crate) name: String,
+    pub(crate) id: String,
+    pub(crate) project: String,
+    #[serde(default)]
+    pub(crate) size: u64,
+    pub(crate) state: String,
+    pub(crate) modified: i64,
+}
+
+pub(crate) struct AppRun<'a> {
+    pub(crate) name: &'a str,
+    pub(crate) inputs_file: &'a Path,
+    pub(crate) folder: &'a str,
+    pub(crate) instance_type: &'a str,
+    pub(crate) app_path: &'a str,
+    pub(crate) extra_args: &'a [String],
+}
+
+pub(crate) struct JobQuery<'a> {
+    pub(crate) created_after: i64,
+    pub(crate) project: &'a str,
+    pub(crate) name_prefix: Option<&'a str>,
+    pub(crate) launched_by: Option<&'a str>,
+}
+
+pub(crate) type OnJob<'a> = dyn FnMut(JobInfo) -> Result<(), Error> + 'a;
+
+const FIND_JOBS_PAGE_SIZE: usize = 1000;
+const JOB_DESCRIBE_FIELDS: [&str; 11] =
+    ["id", "name", "state", "created", "instanceType", "failureReason", "failureMessage",
+        "startedRunning", "stoppedRunning", "totalPrice", "launchedBy"];
+
+pub(crate) trait Platform {
+    fn get_project(&self) -> Result<String, Error>;
+    fn list_folder(&self, folder: &str) -> Result<Vec<String>, Error>;
+    fn find_files(&self, folder: &str) -> Result<Vec<FileInfo>, Error>;
+    fn get_executable_class(&self, path: &str) -> Result<String, Error>;
+    fn find_jobs(&self, query: &JobQuery, on_job: &mut OnJob) -> Result<(), Error>;
+    fn run_app(&self, app_run: &AppRun) -> Result<String, Error>;
+    fn job_log(&self, job_id: &str) -> Result<String, Error>;
+    fn terminate_job(&self, job_id: &str) -> Result<(), Error>;
+    fn download(&self, remote_path: &str, local_file: &Path) -> Result<(), Error>;
+    fn upload(&self, local_file: &Path, remote_path: &str) -> Result<String, Error>;
+    fn remove_files(&self, ids: &[String]) -> Result<(), Error>;
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct DnaNexusLink {
+    pub(crate) id: String,
+    pub(crate) project: String,
+}
+
+#[derive(Serialize)]
+pub(crate) struct JobOutputRef {
+    job: String,
+    field: String,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum LinkTarget {
+    File(DnaNexusLink),
+    JobOutput(JobOutputRef),
+}
+
+#[derive(Serialize)]
+pub(crate) struct WrappedDnaNexusLink {
+    #[serde(rename = "$dnanexus_link")]
+    dnanexus_link: LinkTarget,
+}
+
+impl WrappedDnaNexusLink {
+    pub(crate) fn job_output(job: &str, field: &str) -> WrappedDnaNexusLink {
+        let job_output_ref = JobOutputRef { job: job.to_string(), field: field.to_string() };
+        WrappedDnaNexusLink { dnanexus_link: LinkTarget::JobOutput(job_output_ref) }
+    }
+}
+
+pub(crate) fn get_platform(spec: &str) -> Result<Box<dyn Platform>, Error> {
+    let mut parts = spec.splitn(2, ':');
+    let kind = parts.next().unwrap_or_default();
+    let arg = parts.next();
+    match (kind, arg) {
+        (names::DX, None) => { Ok(Box::new(DxCli::new(DxMode::Live))) }
+        (names::RECORD, Some(cassette)) => { Ok(Box::new(dx::record(Path::new(cassette))?)) }
+        (names::REPLAY, Some(cassette)) => { Ok(Box::new(dx::replay(Path::new(cassette))?)) }
+        (names::API, None) => { Ok(Box::new(ApiPlatform::from_env()?)) }
+        (names::FAKE, Some(root)) => { Ok(Box::new(FakePlatform::new(PathBuf::from(root)))) }
+        _ => {
+            Err(Error::from(
+                format!("Unknown platform '{}'. Known platforms are {}, {}, {}:<dir>, \
+                        {}:<cassette> and {}:<cassette>.", spec, names::DX, names::API,
+                        names::FAKE, names::RECORD, names::REPLAY)
+            ))
+        }
+    }
+}
+
+impl JobQuery<'_> {
+    pub(crate) fn matches(&self, name: &str, launched_by: Option<&str>) -> bool {
+        self.name_prefix.map(|prefix| name.starts_with(prefix)).unwrap_or(true)
+            && self.launched_by.map(|user| launched_by == Some(user)).unwrap_or(true)
+    }
+}
+
+fn find_jobs_input(query: &JobQuery, starting: Value) -> Value {
+    let fields: serde_json::Map<String, Value> =
+        JOB_DESCRIBE_FIELDS.iter().map(|field| (field.to_string(), Value::Bool(true))).collect();
+    let mut input = json!({
+        "project": query.project,
+        "created": { "after": query.created_after },
+        "describe": { "fields": fields },
+        "limit": FIND_JOBS_PAGE_SIZE
+    });
+    if let Some(name_prefix) = query.name_prefix {
+        input["name"] = json!({ "glob": format!("{}*", name_prefix) });
+    }
+    if let Some(launched_by) = query.launched_by {
+        input["launchedBy"] = json!(launched_by);
+    }
+    if !starting.is_null() {
+        input["starting"] = starting;
+    }
+    input
+}
+
+pub(crate) fn page_through_jobs(query: &JobQuery, on_job: &mut OnJob,
+                                find_page: &dyn Fn(&Value) -> Result<Value, Error>)
+                                -> Result<(), Error> {
+    let mut starting = Value::Null;
+    loop {
+        let input = find_jobs_input(query, starting);
+        let mut output = find_page(&input)?;
+        if let Some(results) = output["results"].as_array_mut() {
+            for result in results {
+                on_job(serde_json::from_value(result["describe"].take())?)?
+            }
+        }
+        starting = output["next"].take();
+        if starting.is_null() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn path_to_str(path: &Path) -> Result<&str, Error> {
+    path.to_str().ok_or_else(|| {
+        Error::from(format!("Could not convert path '{}' to string.", path.to_string_lossy()))
+    })
+}
+
+pub(crate) struct FileLinks {
+    folder: String,
+    links_by_name: HashMap<String, DnaNexusLink>,
+}
+
+impl FileLinks {
+    pub(crate) fn new(folder: &str, links_by_name: HashMap<String, DnaNexusLink>) -> FileLinks {
+        let folder = folder.to_string();
+        FileLinks { folder, links_by_name }
+    }
+    pub(crate) fn get(&self, name: &str) -> Result<DnaNexusLink, Error> {
+        self.links_by_name.get(name).cloned().ok_or_else(|| {
+            Error::from(format!("Could not find file '{}' in '{}'.", name, self.folder))
+        })
+    }
+    pub(crate) fn get_wrapped(&self, name: &str) -> Result<WrappedDnaNexusLink, Error> {
+        let dnanexus_link = LinkTarget::File(self.get(name)?);
+        Ok(WrappedDnaNexusLink { dnanexus_link })
+    }
+}
@@ -0,0 +1,455 @@
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::thread;
+use serde_json::{json, Value};
+use ureq::Agent;
+use crate::dx::DxError;
+use crate::env;
+use crate::error::{DxErrorKind, Error};
+use crate::platform::{AppRun, FileInfo, JobQuery, OnJob, page_through_jobs, Platform};
+use crate::retry;
+
+mod keys {
+    pub(crate) const SECURITY_CONTEXT: &str = "DX_SECURITY_CONTEXT";
+    pub(crate) const APISERVER_PROTOCOL: &str = "DX_APISERVER_PROTOCOL";
+    pub(crate) const APISERVER_HOST: &str = "DX_APISERVER_HOST";
+    pub(crate) const APISERVER_PORT: &str = "DX_APISERVER_PORT";
+    pub(crate) const PROJECT_CONTEXT_ID: &str = "DX_PROJECT_CONTEXT_ID";
+}
+
+mod defaults {
+    pub(crate) const APISERVER_PROTOCOL: &str = "https";
+    pub(crate) const APISERVER_HOST: &str = "api.dnanexus.com";
+    pub(crate) const APISERVER_PORT: &str = "443";
+}
+
+pub(crate) struct ApiPlatform {
+    agent: Agent,
+    api_url: String,
+    auth_header: String,
+    project: String,
+}
+
+struct FileDescription {
+    id: String,
+    project: String,
+}
+
+struct DxEnvironment {
+    environment_file: Option<Value>,
+}
+
+impl DxEnvironment {
+    fn read() -> Result<DxEnvironment, Error> {
+        let file = format!("{}/.dnanexus_config/environment.json", env::get_home()?);
+        let environment_file =
+            if Path::new(&file).exists() {
+                Some(serde_json::from_str(&fs::read_to_string(file)?)?)
+            } else {
+                None
+            };
+        Ok(DxEnvironment { environment_file })
+    }
+    fn get(&self, key: &str) -> Option<String> {
+        env::get_opt(key).or_else(|| {
+            self.environment_file.as_ref()
+                .and_then(|file| { file[key].as_str().map(|value| value.to_string()) })
+        })
+    }
+    fn get_required(&self, key: &str) -> Result<String, Error> {
+        self.get(key).ok_or_else(|| {
+            Error::from(format!("{} is not set in the environment or in the dx config.", key))
+        })
+    }
+}
+
+fn split_project_path<'a>(path: &'a str, default_project: &'a str) -> (&'a str, &'a str) {
+    match path.split_once(':') {
+        None => { (default_project, path) }
+        Some((project, path)) => { (project, path) }
+    }
+}
+
+fn split_folder_name(path: &str) -> (&str, &str) {
+    match path.rsplit_once('/') {
+        None => { ("/", path) }
+        Some(("", name)) => { ("/", name) }
+        Some((folder, name)) => { (folder, name) }
+    }
+}
+
+fn trim_folder(folder: &str) -> &str {
+    let trimmed = folder.trim_end_matches('/');
+    if trimmed.is_empty() { "/" } else { trimmed }
+}
+
+fn classify_status(status: u16, message: &str) -> DxErrorKind {
+    match status {
+        429 => { DxErrorKind::RateLimited }
+        403 => { DxErrorKind::PermissionDenied }
+        404 => { DxErrorKind::NotFound }
+        500..=599 => { DxErrorKind::Network }
+        _ => {
+            match DxError::classify(message) {
+                DxErrorKind::Other if status == 401 => { DxErrorKind::AuthExpired }
+                kind => { kind }
+            }
+        }
+    }
+}
+
+fn api_error(route: &str, error: ureq::Error) -> Error {
+    let dx_error =
+        match error {
+            ureq::Error::Status(status, response) => {
+                let body = response.into_string().unwrap_or_default();
+                let message =
+                    serde_json::from_str::<Value>(&body).ok().and_then(|value| {
+                        let error_type = value["error"]["type"].as_str()?.to_string();
+                        let message = value["error"]["message"].as_str()?.to_string();
+                        Some(format!("{}: {}", error_type, message))
+                    }).unwrap_or(body);
+                let kind = classify_status(status, &message);
+                let message = format!("API call {} failed ({}): {}", route, status, message);
+                DxError { kind, message }
+            }
+            ureq::Error::Transport(transport) => {
+                let kind = DxErrorKind::Network;
+                let message = format!("API call {} failed: {}", route, transport);
+                DxError { kind, message }
+            }
+        };
+    Error::from(dx_error)
+}
+
+impl ApiPlatform {
+    pub(crate) fn from_env() -> Result<ApiPlatform, Error> {
+        let dx_env = DxEnvironment::read()?;
+        let security_context: Value =
+            serde_json::from_str(&dx_env.get_required(keys::SECURITY_CONTEXT)?)?;
+        let token_type = security_context["auth_token_type"].as_str().unwrap_or("Bearer");
+        let token =
+            security_context["auth_token"].as_str().ok_or_else(|| {
+                Error::from(format!("No auth_token in {}.", keys::SECURITY_CONTEXT))
+            })?;
+        let auth_header = format!("{} {}", token_type, token);
+        let protocol =
+            dx_env.get(keys::APISERVER_PROTOCOL)
+                .unwrap_or(defaults::APISERVER_PROTOCOL.to_string());
+        let host =
+            dx_env.get(keys::APISERVER_HOST).unwrap_or(defaults::APISERVER_HOST.to_string());
+        let port =
+            dx_env.get(keys::APISERVER_PORT).unwrap_or(defaults::APISERVER_PORT.to_string());
+        let api_url = format!("{}://{}:{}", protocol, host, port);
+        let project = dx_env.get_required(keys::PROJECT_CONTEXT_ID)?;
+        let agent = Agent::new();
+        Ok(ApiPlatform { agent, api_url, auth_header, project })
+    }
+    fn call(&self, route: &str, input: &Value) -> Result<Value, Error> {
+        self.call_retrying(route, input, retry::is_transient)
+    }
+    fn call_retrying(&self, route: &str, input: &Value, should_retry: fn(DxErrorKind) -> bool)
+                     -> Result<Value, Error> {
+        retry::with_retries(route, should_retry, thread::sleep, || self.call_once(route, input))
+    }
+    fn call_once(&self, route: &str, input: &Value) -> Result<Value, Error> {
+        let url = format!("{}/{}", self.api_url, route);
+        let response =
+            self.agent.post(&url)
+                .set("Authorization", &self.auth_header)
+                .send_json(input)
+                .map_err(|error| { api_error(route, error) })?;
+        Ok(response.into_json::<Value>()?)
+    }
+    fn resolve_data_object(&self, path: &str) -> Result<FileDescription, Error> {
+        let (project, path) = split_project_path(path, &self.project);
+        let (folder, name) = split_folder_name(path);
+        let input = json!({
+            "project": project,
+            "objects": [{ "name": name, "folder": trim_folder(folder) }]
+        });
+        let output = self.call("system/resolveDataObjects", &input)?;
+        let result = &output["results"][0][0];
+        let id = result["id"].as_str().ok_or_else(|| {
+            Error::from(format!("Could not resolve '{}'.", path))
+        })?.to_string();
+        let project = result["project"].as_str().unwrap_or(project).to_string();
+        Ok(FileDescription { id, project })
+    }
+}
+
+impl Platform for ApiPlatform {
+    fn get_project(&self) -> Result<String, Error> {
+        Ok(self.project.clone())
+    }
+
+    fn list_folder(&self, folder: &str) -> Result<Vec<String>, Error> {
+        let (project, folder) = split_project_path(folder, &self.project);
+        let input = json!({
+            "folder": trim_folder(folder),
+            "describe": { "fields": { "name": true } }
+        });
+        let output = self.call(&format!("{}/listFolder", project), &input)?;
+        let mut names: Vec<String> = Vec::new();
+        if let Some(folders) = output["folders"].as_array() {
+            for folder in folders.iter().filter_map(|folder| folder.as_str()) {
+                let (_, name) = split_folder_name(folder);
+                names.push(format!("{}/", name))
+            }
+        }
+        if let Some(objects) = output["objects"].as_array() {
+            for object in objects {
+                if let Some(name) = object["describe"]["name"].as_str() {
+                    names.push(name.to_string())
+                }
+            }
+        }
+        Ok(names)
+    }
+
+    fn find_files(&self, folder: &str) -> Result<Vec<FileInfo>, Error> {
+        let (project, folder) = split_project_path(folder, &self.project);
+        let input = json!({
+            "folder": trim_folder(folder),
+            "only": "objects",
+            "describe": {
+                "fields": {
+                    "id": true, "project": true, "class": true, "name": true, "size": true,
+                    "state": true, "modified": true
+                }
+            }
+        });
+        let mut output = self.call(&format!("{}/listFolder", project), &input)?;
+        let mut files: Vec<FileInfo> = Vec::new();
+        if let Some(objects) = output["objects"].as_array_mut() {
+            for object in objects {
+                if object["describe"]["class"].as_str() == Some("file") {
+                    files.push(serde_json::from_value(object["describe"].take())?)
+                }
+            }
+        }
+        Ok(files)
+    }
+
+    fn get_executable_class(&self, path: &str) -> Result<String, Error> {
+        let id =
+            if path.starts_with("app-") || path.starts_with("applet-") {
+                path.to_string()
+            } else {
+                self.resolve_data_object(path)?.id
+            };
+        let output = self.call(&format!("{}/describe", id), &json!({}))?;
+        output["class"].as_str().map(|class| class.to_string()).ok_or_else(|| {
+            Error::from(format!("Could not get class of '{}'.", path))
+        })
+    }
+
+    fn find_jobs(&self, query: &JobQuery, on_job: &mut OnJob) -> Result<(), Error> {
+        page_through_jobs(query, on_job, &|input| { self.call("system/findJobs", input) })
+    }
+
+    fn run_app(&self, app_run: &AppRun) -> Result<String, Error> {
+        if !app_run.extra_args.is_empty() {
+            Err(Error::from(format!("Extra dx run arguments ({}) need the dx platform.",
+                                    app_run.extra_args.join(" "))))?
+        }
+        let executable =
+            if app_run.app_path.starts_with("app-") || app_run.app_path.starts_with("applet-") {
+                app_run.app_path.to_string()
+            } else {
+                self.resolve_data_object(app_run.app_path)?.id
+            };
+        let (project, folder) = split_project_path(app_run.folder, &self.project);
+        let input: Value = serde_json::from_str(&fs::read_to_string(app_run.inputs_file)?)?;
+        let run_input = json!({
+            "name": app_run.name,
+            "input": input,
+            "project": project,
+            "folder": trim_folder(folder),
+            "systemRequirements": { "*": { "instanceType": app_run.instance_type } }
+        });
+        let output =
+            self.call_retrying(&format!("{}/run", executable), &run_input, retry::is_rejected)?;
+        output["id"].as_str().map(|id| id.to_string()).ok_or_else(|| {
+            Error::from(format!("No job ID in response to running {}.", executable))
+        })
+    }
+
+    fn job_log(&self, job_id: &str) -> Result<String, Error> {
+        Err(Error::from(format!("The api platform does not support job logs. Use '--platform dx' \
+            or run 'dx watch {}' to see the log.", job_id)))
+    }
+
+    fn terminate_job(&self, job_id: &str) -> Result<(), Error> {
+        self.call(&format!("{}/terminate", job_id), &json!({}))?;
+        Ok(())
+    }
+
+    fn download(&self, remote_path: &str, local_file: &Path) -> Result<(), Error> {
+        let FileDescription { id, project } = self.resolve_data_object(remote_path)?;
+        let output =
+            self.call(&format!("{}/download", id), &json!({ "project": project }))?;
+        let url =
+            output["url"].as_str().ok_or_else(|| {
+                Error::from(format!("No download URL for {}.", remote_path))
+            })?;
+        let mut request = self.agent.get(url);
+        if let Some(headers) = output["headers"].as_object() {
+            for (name, value) in headers {
+                if let Some(value) = value.as_str() {
+                    request = request.set(name, value)
+                }
+            }
+        }
+        let response = request.call().map_err(|error| { api_error(url, error) })?;
+        let mut file = File::create(local_file)?;
+        io::copy(&mut response.into_reader(), &mut file)?;
+        Ok(())
+    }
+
+    fn upload(&self, local_file: &Path, remote_path: &str) -> Result<String, Error> {
+        let (project, path) = split_project_path(remote_path, &self.project);
+        let (folder, name) = split_folder_name(path);
+        let new_input = json!({
+            "project": project, "folder": trim_folder(folder), "name": name, "parents": true
+        });
+        let output = self.call_retrying("file/new", &new_input, retry::is_rejected)?;
+        let id =
+            output["id"].as_str().ok_or_else(|| {
+                Error::from(format!("No file ID in response to creating {}.", remote_path))
+            })?.to_string();
+        let contents = fs::read(local_file)?;
+        let upload_input = json!({
+            "index": 1, "size": contents.len(), "md5": format!("{:x}", md5::compute(&contents))
+        });
+        let output = self.call(&format!("{}/upload", id), &upload_input)?;
+        let url =
+            output["url"].as_str().ok_or_else(|| {
+                Error::from(format!("No upload URL for {}.", remote_path))
+            })?;
+        let mut request = self.agent.put(url);
+        if let Some(headers) = output["headers"].as_object() {
+            for (name, value) in headers {
+                if let Some(value) = value.as_str() {
+                    request = request.set(name, value)
+                }
+            }
+        }
+        request.send_bytes(&contents).map_err(|error| { api_error(url, error) })?;
+        self.call(&format!("{}/close", id), &json!({}))?;
+        Ok(id)
+    }
+
+    fn remove_files(&self, ids: &[String]) -> Result<(), Error> {
+        let input = json!({ "objects": ids });
+        self.call(&format!("{}/removeObjects", self.project), &input)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+    use std::thread::JoinHandle;
+    use serde_json::{json, Value};
+    use ureq::Agent;
+    use crate::error::{DxErrorKind, ErrorKind};
+    use crate::platform::{JobQuery, Platform};
+    use super::ApiPlatform;
+
+    type Request = (String, String, Value);
+
+    fn serve(responses: Vec<(u16, Value)>) -> (String, JoinHandle<Vec<Request>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let api_url = format!("http://{}", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let mut requests: Vec<Request> = Vec::new();
+            for (status, body) in responses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let route = request_line.split(' ').nth(1).unwrap().to_string();
+                let mut auth_header = String::new();
+                let mut content_length: usize = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let line = line.trim_end();
+                    if line.is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(": ") {
+                        match name.to_lowercase().as_str() {
+                            "authorization" => { auth_header = value.to_string() }
+                            "content-length" => { content_length = value.parse().unwrap() }
+                            _ => {}
+                        }
+                    }
+                }
+                let mut input = vec![0u8; content_length];
+                reader.read_exact(&mut input).unwrap();
+                requests.push((route, auth_header, serde_json::from_slice(&input).unwrap()));
+                let body = body.to_string();
+                write!(reader.get_mut(), "HTTP/1.1 {} Status\r\nContent-Type: application/json\r\n\
+                    Content-Length: {}\r\nConnection: close\r\n\r\n{}", status, body.len(), body)
+                    .unwrap();
+            }
+            requests
+        });
+        (api_url, server)
+    }
+
+    fn platform(api_url: String) -> ApiPlatform {
+        let auth_header = "Bearer token".to_string();
+        ApiPlatform { agent: Agent::new(), api_url, auth_header, project: "project-1".to_string() }
+    }
+
+    fn job(id: &str, name: &str) -> Value {
+        json!({ "describe": { "id": id, "name": name, "state": "done", "created": 1 } })
+    }
+
+    #[test]
+    fn find_jobs_pages_through_results() {
+        let (api_url, server) = serve(vec![
+            (200, json!({ "results": [job("job-1", "vcfs2bed_c1_b0")], "next": "cursor-1" })),
+            (200, json!({ "results": [job("job-2", "vcfs2bed_c1_b1")], "next": null })),
+        ]);
+        let query = JobQuery {
+            created_after: 5, project: "project-1", name_prefix: Some("vcfs2bed"),
+            launched_by: None,
+        };
+        let mut ids: Vec<String> = Vec::new();
+        platform(api_url).find_jobs(&query, &mut |job| {
+            ids.push(job.id);
+            Ok(())
+        }).unwrap();
+        assert_eq!(ids, vec!["job-1", "job-2"]);
+        let requests = server.join().unwrap();
+        assert!(requests.iter().all(|(route, auth_header, _)| {
+            route == "/system/findJobs" && auth_header == "Bearer token"
+        }));
+        assert_eq!(requests[0].2["created"]["after"], json!(5));
+        assert_eq!(requests[0].2["name"]["glob"], json!("vcfs2bed*"));
+        assert!(requests[0].2.get("starting").is_none());
+        assert_eq!(requests[1].2["starting"], json!("cursor-1"));
+    }
+
+    #[test]
+    fn missing_folder_is_not_found() {
+        let error_body = json!({
+            "error": { "type": "ResourceNotFound", "message": "The folder could not be found" }
+        });
+        let (api_url, server) = serve(vec![(404, error_body)]);
+        let error = platform(api_url).find_files("/missing/").err().unwrap();
+        assert!(matches!(error.kind(), ErrorKind::Dx(DxErrorKind::NotFound)), "{}", error);
+        assert!(error.to_string().contains("ResourceNotFound"), "{}", error);
+        let requests = server.join().unwrap();
+        assert_eq!(requests[0].0, "/project-1/listFolder");
+        assert_eq!(requests[0].2["folder"], json!("/missing"));
+    }
+}
@@ -0,0 +1,227 @@
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde_json::{json, Value};
+use crate::dx::DxError;
+use crate::error::{DxErrorKind, Error};
+use crate::platform::{AppRun, FileInfo, JobQuery, OnJob, Platform};
+
+mod names {
+    pub(crate) const FILES_DIR: &str = "files";
+    pub(crate) const JOBS_FILE: &str = "jobs.json";
+    pub(crate) const LOGS_DIR: &str = "logs";
+    pub(crate) const PROJECT_FILE: &str = "project";
+    pub(crate) const DEFAULT_PROJECT: &str = "project-fake";
+    pub(crate) const FAKE_USER: &str = "user-fake";
+}
+
+pub(crate) struct FakePlatform {
+    root: PathBuf,
+}
+
+impl FakePlatform {
+    pub(crate) fn new(root: PathBuf) -> FakePlatform {
+        FakePlatform { root }
+    }
+    fn local_path(&self, remote_path: &str) -> PathBuf {
+        let remote_path = remote_path.split_once(':').map(|(_, path)| path).unwrap_or(remote_path);
+        self.root.join(names::FILES_DIR).join(remote_path.trim_start_matches('/'))
+    }
+    fn read_folder(&self, folder: &str) -> Result<fs::ReadDir, Error> {
+        fs::read_dir(self.local_path(folder)).map_err(|error| {
+            if error.kind() == io::ErrorKind::NotFound {
+                let kind = DxErrorKind::NotFound;
+                let message = format!("Could not resolve folder '{}'.", folder);
+                Error::from(DxError { kind, message })
+            } else {
+                Error::from(error)
+            }
+        })
+    }
+    fn jobs_file(&self) -> PathBuf {
+        self.root.join(names::JOBS_FILE)
+    }
+    fn read_jobs(&self) -> Result<Vec<Value>, Error> {
+        let jobs_file = self.jobs_file();
+        if jobs_file.exists() {
+            Ok(serde_json::from_str(&fs::read_to_string(jobs_file)?)?)
+        } else {
+            Ok(Vec::new())
+        }
+    }
+    fn write_jobs(&self, jobs: &[Value]) -> Result<(), Error> {
+        fs::create_dir_all(&self.root)?;
+        fs::write(self.jobs_file(), serde_json::to_string_pretty(jobs)?)?;
+        Ok(())
+    }
+}
+
+fn fake_id(prefix: &str, key: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    format!("{}-{:024x}", prefix, hasher.finish())
+}
+
+impl Platform for FakePlatform {
+    fn get_project(&self) -> Result<String, Error> {
+        let project_file = self.root.join(names::PROJECT_FILE);
+        if project_file.exists() {
+            Ok(fs::read_to_string(project_file)?.trim().to_string())
+        } else {
+            Ok(names::DEFAULT_PROJECT.to_string())
+        }
+    }
+
+    fn list_folder(&self, folder: &str) -> Result<Vec<String>, Error> {
+        let mut entries: Vec<String> = Vec::new();
+        for entry in self.read_folder(folder)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            if entry.file_type()?.is_dir() {
+                entries.push(format!("{}/", name))
+            } else {
+                entries.push(name)
+            }
+        }
+        entries.sort();
+        Ok(entries)
+    }
+
+    fn find_files(&self, folder: &str) -> Result<Vec<FileInfo>, Error> {
+        let project = self.get_project()?;
+        let mut files: Vec<FileInfo> = Vec::new();
+        for entry in self.read_folder(folder)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            if metadata.is_file() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                let path = format!("{}/{}", folder.trim_end_matches('/'), name);
+                let id = fake_id("file", &path);
+                let project = project.clone();
+                let size = metadata.len();
+                let state = "closed".to_string();
+                let modified = metadata.modified()?.duration_since(UNIX_EPOCH)?.as_millis() as i64;
+                files.push(FileInfo { name, id, project, size, state, modified })
+            }
+        }
+        Ok(files)
+    }
+
+    fn get_executable_class(&self, path: &str) -> Result<String, Error> {
+        if let Some((class, _)) = path.split_once('-') {
+            if class == "app" || class == "applet" {
+                return Ok(class.to_string())
+            }
+        }
+        if self.local_path(path).is_file() {
+            Ok("applet".to_string())
+        } else {
+            let kind = DxErrorKind::NotFound;
+            let message = format!("Could not resolve '{}'.", path);
+            Err(Error::from(DxError { kind, message }))
+        }
+    }
+
+    fn find_jobs(&self, query: &JobQuery, on_job: &mut OnJob) -> Result<(), Error> {
+        for job in self.read_jobs()? {
+            let name = job["name"].as_str().unwrap_or_default();
+            if job["project"].as_str() == Some(query.project)
+                && job["created"].as_i64().unwrap_or_default() >= query.created_after
+                && query.matches(name, job["launchedBy"].as_str()) {
+                on_job(serde_json::from_value(job)?)?
+            }
+        }
+        Ok(())
+    }
+
+    fn run_app(&self, app_run: &AppRun) -> Result<String, Error> {
+        let mut jobs = self.read_jobs()?;
+        let id = fake_id("job", &format!("{}-{}", app_run.name, jobs.len()));
+        let input: Value = serde_json::from_str(&fs::read_to_string(app_run.inputs_file)?)?;
+        let project =
+            match app_run.folder.split_once(':') {
+                None => { self.get_project()? }
+                Some((project, _)) => { project.to_string() }
+            };
+        jobs.insert(0, json!({
+            "id": id,
+            "name": app_run.name,
+            "state": "runnable",
+            "launchedBy": names::FAKE_USER,
+            "created": SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as i64,
+            "executable": app_run.app_path,
+            "project": project,
+            "folder": app_run.folder,
+            "instanceType": app_run.instance_type,
+            "extraArgs": app_run.extra_args,
+            "input": input
+        }));
+        self.write_jobs(&jobs)?;
+        Ok(id)
+    }
+
+    fn job_log(&self, job_id: &str) -> Result<String, Error> {
+        let log_file = self.root.join(names::LOGS_DIR).join(job_id);
+        if !log_file.is_file() {
+            let kind = DxErrorKind::NotFound;
+            let message = format!("No log for job {}.", job_id);
+            Err(Error::from(DxError { kind, message }))?
+        }
+        Ok(fs::read_to_string(log_file)?)
+    }
+
+    fn terminate_job(&self, job_id: &str) -> Result<(), Error> {
+        let mut jobs = self.read_jobs()?;
+        match jobs.iter_mut().find(|job| job["id"].as_str() == Some(job_id)) {
+            None => {
+                let kind = DxErrorKind::NotFound;
+                let message = format!("No job {}.", job_id);
+                Err(Error::from(DxError { kind, message }))?
+            }
+            Some(job) => { job["state"] = json!("terminated") }
+        }
+        self.write_jobs(&jobs)
+    }
+
+    fn download(&self, remote_path: &str, local_file: &Path) -> Result<(), Error> {
+        let local_path = self.local_path(remote_path);
+        if !local_path.is_file() {
+            let kind = DxErrorKind::NotFound;
+            let message = format!("Could not resolve '{}' to a file.", remote_path);
+            Err(Error::from(DxError { kind, message }))?
+        }
+        fs::copy(local_path, local_file)?;
+        Ok(())
+    }
+
+    fn upload(&self, local_file: &Path, remote_path: &str) -> Result<String, Error> {
+        let local_path = self.local_path(remote_path);
+        if let Some(parent) = local_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(local_file, local_path)?;
+        Ok(fake_id("file", remote_path))
+    }
+
+    fn remove_files(&self, ids: &[String]) -> Result<(), Error> {
+        let files_dir = self.root.join(names::FILES_DIR);
+        let mut dirs: Vec<PathBuf> = vec![files_dir.clone()];
+        while let Some(dir) = dirs.pop() {
+            for entry in fs::read_dir(dir)? {
+                let path = entry?.path();
+                if path.is_dir() {
+                    dirs.push(path)
+                } else if let Ok(relative) = path.strip_prefix(&files_dir) {
+                    let remote_path = format!("/{}", relative.to_string_lossy());
+                    if ids.contains(&fake_id("file", &remote_path)) {
+                        fs::remove_file(&path)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,89 @@
+use serde::Serialize;
+use serde_json::{Map, Value};
+use crate::error::Error;
+use crate::selection::Format;
+
+fn csv_cell(cell: &str) -> String {
+    if cell.contains([',', '"', '\n']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell.to_string()
+    }
+}
+
+fn tsv_cell(cell: &str) -> String {
+    cell.replace(['\t', '\n'], " ")
+}
+
+fn value_cell(value: &Value) -> String {
+    match value {
+        Value::Null => { String::new() }
+        Value::String(string) => { string.clone() }
+        value => { value.to_string() }
+    }
+}
+
+fn row_fields<R: Serialize>(row: &R) -> Result<Map<String, Value>, Error> {
+    match serde_json::to_value(row)? {
+        Value::Object(fields) => { Ok(fields) }
+        _ => { Err(Error::from("Only structs can be printed as table rows.")) }
+    }
+}
+
+fn table_lines<R: Serialize + Default>(rows: &[R], separator: &str, escape: fn(&str) -> String)
+                                       -> Result<Vec<String>, Error> {
+    let header: Vec<String> =
+        row_fields(&R::default())?.keys().map(|column| escape(column)).collect();
+    let mut lines: Vec<String> = vec![header.join(separator)];
+    for row in rows {
+        let cells: Vec<String> =
+            row_fields(row)?.values().map(|value| escape(&value_cell(value))).collect();
+        lines.push(cells.join(separator));
+    }
+    Ok(lines)
+}
+
+fn print_table<R: Serialize + Default>(rows: &[R], separator: &str, escape: fn(&str) -> String)
+                                       -> Result<(), Error> {
+    for line in table_lines(rows, separator, escape)? {
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+pub(crate) fn print_rows<R: Serialize + Default>(format: Format, rows: &[R],
+                                                 print_text: impl FnOnce()) -> Result<(), Error> {
+    match format {
+        Format::Text => { print_text() }
+        Format::Json => { println!("{}", serde_json::to_string_pretty(rows)?) }
+        Format::Tsv => { print_table(rows, "\t", tsv_cell)? }
+        Format::Csv => { print_table(rows, ",", csv_cell)? }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Serialize;
+    use super::{csv_cell, table_lines, tsv_cell};
+
+    #[derive(Serialize, Default)]
+    struct Row {
+        name: String,
+        note: Option<String>,
+        count: usize,
+    }
+
+    #[test]
+    fn empty_tables_still_have_a_header() {
+        assert_eq!(table_lines::<Row>(&[], ",", csv_cell).unwrap(), vec!["name,note,count"]);
+    }
+
+    #[test]
+    fn cells_are_escaped() {
+        let rows = [Row { name: "a,\"b\"".to_string(), note: None, count: 2 }];
+        assert_eq!(table_lines(&rows, ",", csv_cell).unwrap()[1], "\"a,\"\"b\"\"\",,2");
+        let rows = [Row { name: "a\tb".to_string(), note: Some("c".to_string()), count: 0 }];
+        assert_eq!(table_lines(&rows, "\t", tsv_cell).unwrap()[1], "a b\tc\t0");
+    }
+}
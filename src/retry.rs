@@ -0,0 +1,45 @@
+use std::time::Duration;
+use crate::error::{DxErrorKind, Error, ErrorKind};
+
+const MAX_ATTEMPTS: u32 = 6;
+const INITIAL_DELAY_MILLIS: u64 = 1000;
+const MAX_DELAY_MILLIS: u64 = 60000;
+
+fn delay(i_attempt: u32) -> Duration {
+    let millis = INITIAL_DELAY_MILLIS.saturating_mul(1 << i_attempt.min(16));
+    Duration::from_millis(millis.min(MAX_DELAY_MILLIS))
+}
+
+pub(crate) fn with_retries<T, R, F>(description: &str, should_retry: R, pause: fn(Duration),
+                                    mut action: F) -> Result<T, Error>
+    where R: Fn(DxErrorKind) -> bool, F: FnMut() -> Result<T, Error> {
+    let mut i_attempt: u32 = 0;
+    loop {
+        match action() {
+            Ok(value) => { return Ok(value) }
+            Err(error) => {
+                let retryable =
+                    match error.kind() {
+                        ErrorKind::Dx(dx_error_kind) => { should_retry(dx_error_kind) }
+                        _ => { false }
+                    };
+                i_attempt += 1;
+                if !retryable || i_attempt >= MAX_ATTEMPTS {
+                    return Err(error)
+                }
+                let delay = delay(i_attempt - 1);
+                eprintln!("{} failed ({}), attempt {} of {}. Retrying in {}s.", description,
+                          error, i_attempt, MAX_ATTEMPTS, delay.as_secs());
+                pause(delay);
+            }
+        }
+    }
+}
+
+pub(crate) fn is_transient(dx_error_kind: DxErrorKind) -> bool {
+    dx_error_kind.is_transient()
+}
+
+pub(crate) fn is_rejected(dx_error_kind: DxErrorKind) -> bool {
+    dx_error_kind == DxErrorKind::RateLimited
+}
@@ -1,10 +1,23 @@
+use crate::error::Error;
+
 pub struct Selection {
     pub choice: Choice,
     pub params: Params,
 }
 
 pub struct Params {
-    pub conf_file: String,
+    pub conf_file: Option<String>,
+    pub platform: String,
+    pub profile: Option<String>,
+    pub format: Format,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Format {
+    Text,
+    Json,
+    Tsv,
+    Csv,
 }
 
 pub enum DataSet {
@@ -14,9 +27,10 @@ pub enum DataSet {
 
 pub enum Choice {
     Data { data_set: DataSet, data_choice: DataChoice },
-    Vcfs2Bed(AppChoice),
-    BedMerge(AppChoice),
+    App { app: String, app_choice: AppChoice },
+    Pipeline(RunChoice),
     Config(Config),
+    Cache(CacheChoice),
 }
 
 pub enum DataChoice {
@@ -26,16 +40,65 @@ pub enum DataChoice {
 
 pub enum AppChoice {
     Run(RunChoice),
-    Monitor,
+    Monitor(MonitorChoice),
+    Logs { job_name: String },
+    Failures,
+    Costs,
+    Terminate(TerminateChoice),
+    Reconcile { dry: bool },
+    Verify,
 }
 
 pub struct RunChoice {
     pub num: Option<usize>,
     pub dry: bool,
     pub pat: Option<String>,
+    pub names: Option<String>,
+}
+
+pub struct MonitorChoice {
+    pub watch: bool,
+    pub interval: Option<u64>,
+}
+
+pub struct TerminateChoice {
+    pub pat: Option<String>,
+    pub states: Vec<String>,
+    pub dry: bool,
+    pub yes: bool,
 }
 
 pub enum Config {
-    Download
+    Download,
+    Show { origin: bool },
+    Validate,
+    Upload { file: Option<String> },
+    Diff,
+}
+
+pub enum CacheChoice {
+    Show,
+    Clear,
+}
+
+
+impl Format {
+    pub const NAMES: [&'static str; 4] = ["text", "json", "tsv", "csv"];
 }
 
+impl TryFrom<&str> for Format {
+    type Error = Error;
+
+    fn try_from(string: &str) -> Result<Self, Self::Error> {
+        match string {
+            "text" => { Ok(Format::Text) }
+            "json" => { Ok(Format::Json) }
+            "tsv" => { Ok(Format::Tsv) }
+            "csv" => { Ok(Format::Csv) }
+            _ => {
+                Err(Error::from(format!("Unknown format {}. Known formats are {}.", string,
+                                        Format::NAMES.join(", "))))
+            }
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use crate::conf::Conf;
+use crate::dx;
+use crate::dx::DxCli;
+
+const CASSETTES_DIR: &str = "tests/cassettes";
+const VCFS_DIR: &str =
+    "/Bulk/Exome sequences/Population level exome OQFE variants, pVCF format - final release/";
+
+pub(crate) struct TempDir {
+    pub(crate) path: PathBuf,
+}
+
+impl TempDir {
+    pub(crate) fn new(name: &str) -> TempDir {
+        let path = env::temp_dir().join(format!("udix-test-{}-{}", process::id(), name));
+        let _ = fs::remove_dir_all(&path);
+        TempDir { path }
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+pub(crate) fn replay_cassette(name: &str) -> DxCli {
+    let cassette = Path::new(env!("CARGO_MANIFEST_DIR")).join(CASSETTES_DIR).join(name);
+    dx::replay(&cassette).unwrap()
+}
+
+pub(crate) fn cassette_conf(beds_dir: &str, work_dir: &Path) -> Conf {
+    let toml = format!(r#"
+[data]
+vcfs_dir = "{}"
+beds_dir = "{}"
+[workspace]
+work_dir = "{}"
+project = "project-GbZ0Qz8JK4Fq3v6bXyk21vPx"
+[misc]
+start_date = "-2w"
+"#, VCFS_DIR, beds_dir, work_dir.to_string_lossy());
+    toml::from_str(&toml).unwrap()
+}
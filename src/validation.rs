@@ -0,0 +1,231 @@
+use std::fmt::{Display, Formatter};
+use std::collections::BTreeMap;
+use crate::apps::app_settings;
+use crate::apps::spec::{all_specs, AppSpec};
+use crate::conf::Conf;
+use crate::data::chromosome::Chromosome;
+use crate::date::parse_date_millis;
+use crate::error::Error;
+use crate::job::JobState;
+use crate::platform::Platform;
+
+mod keys {
+    pub(crate) const START_DATE: &str = "misc.start_date";
+    pub(crate) const RESUBMIT_STATES: &str = "jobs.resubmit_states";
+    pub(crate) const MAX_ATTEMPTS: &str = "jobs.max_attempts";
+    pub(crate) const SPECS: &str = "specs";
+}
+
+pub(crate) struct Problem {
+    key: String,
+    message: String,
+}
+
+pub(crate) struct Problems {
+    problems: Vec<Problem>,
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.key, self.message)
+    }
+}
+
+impl Problems {
+    fn new() -> Problems {
+        Problems { problems: Vec::new() }
+    }
+    fn add(&mut self, key: &str, message: String) {
+        let key = key.to_string();
+        self.problems.push(Problem { key, message })
+    }
+    fn into_result(self) -> Result<(), Error> {
+        if self.problems.is_empty() {
+            Ok(())
+        } else {
+            let lines: Vec<String> =
+                self.problems.iter().map(|problem| { format!("  {}", problem) }).collect();
+            Err(Error::from(format!("Config has {} problem(s):\n{}", self.problems.len(),
+                                    lines.join("\n"))))
+        }
+    }
+}
+
+fn is_well_formed_instance_type(instance_type: &str) -> bool {
+    let parts: Vec<&str> = instance_type.split('_').collect();
+    let is_numbered = |part: &str, prefix: &str| {
+        part.strip_prefix(prefix)
+            .map(|number| { !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()) })
+            .unwrap_or(false)
+    };
+    let is_storage = |part: &str| { is_numbered(part, "ssd") || is_numbered(part, "hdd") };
+    let is_modifier = |part: &str| {
+        is_numbered(part, "v") || part == "gpu" || is_numbered(part, "gpu")
+    };
+    match parts.as_slice() {
+        [memory, storage, modifiers @ .., cores] => {
+            is_numbered(memory, "mem") && is_storage(storage)
+                && modifiers.iter().all(|part| { is_modifier(part) }) && is_numbered(cores, "x")
+        }
+        _ => { false }
+    }
+}
+
+fn check_folder(platform: &dyn Platform, key: &str, folder: &str, problems: &mut Problems) {
+    match platform.list_folder(folder) {
+        Ok(entries) => {
+            if entries.is_empty() {
+                problems.add(key, format!("Folder '{}' is empty.", folder))
+            }
+        }
+        Err(error) => {
+            problems.add(key, format!("Cannot list folder '{}': {}", folder, error))
+        }
+    }
+}
+
+fn check_instance_type(key: &str, instance_type: &str, problems: &mut Problems) {
+    if !is_well_formed_instance_type(instance_type) {
+        problems.add(key, format!("'{}' is not a well-formed instance type name, such as \
+                                   mem1_ssd1_v2_x4.", instance_type))
+    }
+}
+
+fn check_common(conf: &Conf, problems: &mut Problems) {
+    if let Err(error) = parse_date_millis(&conf.misc.start_date) {
+        problems.add(keys::START_DATE, error.to_string())
+    }
+    for state in &conf.jobs.resubmit_states {
+        match JobState::try_from(state.as_str()) {
+            Ok(job_state) if job_state != JobState::Restartable
+                && (job_state.is_active() || job_state == JobState::Done) => {
+                problems.add(keys::RESUBMIT_STATES,
+                             format!("Resubmitting {} jobs would duplicate work.", state))
+            }
+            Ok(_) => {}
+            Err(error) => { problems.add(keys::RESUBMIT_STATES, error.to_string()) }
+        }
+    }
+    if conf.jobs.max_attempts == 0 {
+        problems.add(keys::MAX_ATTEMPTS, "Must allow at least one attempt.".to_string())
+    }
+    match all_specs(conf) {
+        Ok(specs) => { check_specs(&specs, problems) }
+        Err(error) => { problems.add(keys::SPECS, error.to_string()) }
+    }
+}
+
+fn check_specs(specs: &BTreeMap<String, AppSpec>, problems: &mut Problems) {
+    for spec in specs.values() {
+        let key_prefix = format!("{}.{}", keys::SPECS, spec.name);
+        if spec.prefix.is_empty() || spec.prefix.contains('_') {
+            problems.add(&format!("{}.prefix", key_prefix),
+                         "Must be non-empty and must not contain '_'.".to_string())
+        }
+        for other in specs.values() {
+            if other.name != spec.name && other.prefix.starts_with(&spec.prefix) {
+                problems.add(&format!("{}.prefix", key_prefix),
+                             format!("Job names of app {} would also match prefix '{}'.",
+                                     other.name, spec.prefix))
+            }
+        }
+        if spec.inputs.is_empty() {
+            problems.add(&format!("{}.inputs", key_prefix), "Needs at least one input.".to_string())
+        }
+        for (field, source) in &spec.outputs {
+            if source.file_extension().is_none() {
+                problems.add(&format!("{}.outputs.{}", key_prefix, field),
+                             format!("Outputs need to be files, not {}.", source))
+            }
+        }
+        for (field, source) in &spec.inputs {
+            if !spec.sharding.provides(*source) {
+                problems.add(&format!("{}.inputs.{}", key_prefix, field),
+                             format!("Sharding {} does not provide {} inputs.", spec.sharding,
+                                     source))
+            }
+        }
+    }
+}
+
+fn check_app(platform: &dyn Platform, app: &AppSpec, problems: &mut Problems) {
+    let settings = app_settings(app, None);
+    let key_prefix = format!("{}.{}", keys::SPECS, app.name);
+    check_instance_type(&format!("{}.instance_type", key_prefix), &settings.instance_type,
+                        problems);
+    for (chromosome, instance_type) in &app.chromosome_instance_types {
+        let key = format!("{}.chromosome_instance_types.{}", key_prefix, chromosome);
+        if let Err(error) = Chromosome::parse(chromosome) {
+            problems.add(&key, format!("'{}' is not a chromosome: {}", chromosome, error))
+        }
+        check_instance_type(&key, instance_type, problems)
+    }
+    for instance_type in &settings.instance_escalation {
+        check_instance_type(&format!("{}.instance_escalation", key_prefix), instance_type,
+                            problems)
+    }
+    let app_path_key = format!("{}.app_path", key_prefix);
+    match platform.get_executable_class(&settings.app_path) {
+        Ok(class) => {
+            if class != "app" && class != "applet" {
+                problems.add(&app_path_key,
+                             format!("'{}' is a {}, not an app or applet.", settings.app_path,
+                                     class))
+            }
+        }
+        Err(error) => {
+            problems.add(&app_path_key,
+                         format!("Cannot resolve '{}': {}", settings.app_path, error))
+        }
+    }
+}
+
+fn check_inputs_folder(conf: &Conf, platform: &dyn Platform, app: &AppSpec,
+                       problems: &mut Problems) {
+    check_folder(platform, app.sharding.inputs_folder_key(),
+                 &conf.in_project(app.inputs_folder(conf)), problems);
+}
+
+pub(crate) fn check_for_app(conf: &Conf, platform: &dyn Platform, app: &AppSpec)
+                            -> Result<(), Error> {
+    let mut problems = Problems::new();
+    check_common(conf, &mut problems);
+    check_app(platform, app, &mut problems);
+    check_inputs_folder(conf, platform, app, &mut problems);
+    problems.into_result()
+}
+
+pub(crate) fn check_for_pipeline(conf: &Conf, platform: &dyn Platform, block_app: &AppSpec,
+                                 merge_app: &AppSpec) -> Result<(), Error> {
+    let mut problems = Problems::new();
+    check_common(conf, &mut problems);
+    check_app(platform, block_app, &mut problems);
+    check_inputs_folder(conf, platform, block_app, &mut problems);
+    check_app(platform, merge_app, &mut problems);
+    problems.into_result()
+}
+
+pub(crate) fn check_all(conf: &Conf, platform: &dyn Platform) -> Result<(), Error> {
+    let mut problems = Problems::new();
+    check_common(conf, &mut problems);
+    for app in all_specs(conf)?.values() {
+        check_app(platform, app, &mut problems);
+        check_inputs_folder(conf, platform, app, &mut problems);
+    }
+    problems.into_result()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_well_formed_instance_type;
+
+    #[test]
+    fn well_formed_instance_type_names() {
+        for name in ["mem1_ssd1_v2_x4", "mem2_hdd2_x8", "mem3_ssd1_gpu_x16", "mem1_ssd2_v2_x72"] {
+            assert!(is_well_formed_instance_type(name), "{}", name)
+        }
+        for name in ["mem1_ssd1_v2", "mem_ssd1_x4", "mem1_nvme1_x4", "mem1_ssd1_v2_x4_", "x4"] {
+            assert!(!is_well_formed_instance_type(name), "{}", name)
+        }
+    }
+}
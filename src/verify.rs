@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use serde::Serialize;
+use crate::apps::app_settings;
+use crate::apps::spec::AppSpec;
+use crate::conf::Conf;
+use crate::error::{DxErrorKind, Error, ErrorKind};
+use crate::job::JobState;
+use crate::monitor;
+use crate::platform::{FileInfo, Platform};
+use crate::report::print_rows;
+use crate::selection::Format;
+
+const CLOSED: &str = "closed";
+
+#[derive(Serialize, Default)]
+struct OutputGap {
+    job_name: String,
+    job_id: String,
+    file: String,
+    problem: String,
+}
+
+fn file_problem(files: Option<&Vec<FileInfo>>) -> Option<String> {
+    match files {
+        None => { Some("missing".to_string()) }
+        Some(files) => {
+            if files.iter().any(|file| file.size > 0 && file.state == CLOSED) {
+                None
+            } else if files.iter().all(|file| file.state != CLOSED) {
+                let states: Vec<&str> = files.iter().map(|file| file.state.as_str()).collect();
+                Some(format!("not closed, but {}", states.join(", ")))
+            } else {
+                Some("empty".to_string())
+            }
+        }
+    }
+}
+
+fn rerun_file_name(app: &AppSpec) -> String {
+    format!("rerun_{}.txt", app.name)
+}
+
+pub(crate) fn verify_outputs(conf: &Conf, platform: &dyn Platform, app: &AppSpec,
+                             format: Format) -> Result<(), Error> {
+    if app.outputs.values().all(|source| source.file_extension().is_none()) {
+        Err(Error::from(format!("App {} declares no output files to verify.", app.name)))?
+    }
+    let settings = app_settings(app, None);
+    let out_dir = format!("{}:{}", conf.project(platform)?, settings.out_dir);
+    let out_files =
+        match platform.find_files(&out_dir) {
+            Err(error) if matches!(error.kind(), ErrorKind::Dx(DxErrorKind::NotFound)) => {
+                Vec::new()
+            }
+            out_files => { out_files? }
+        };
+    let mut files_by_name: HashMap<String, Vec<FileInfo>> = HashMap::new();
+    for file in out_files {
+        files_by_name.entry(file.name.clone()).or_default().push(file)
+    }
+    let mut done_jobs: Vec<monitor::JobInfo> =
+        monitor::jobs_by_name(conf, platform, app)?.into_values()
+            .map(|history| history.latest)
+            .filter(|job| job.state == JobState::Done).collect();
+    done_jobs.sort_by(|job1, job2| job1.name.cmp(&job2.name));
+    let mut gaps: Vec<OutputGap> = Vec::new();
+    let mut rerun_names: Vec<String> = Vec::new();
+    for job in &done_jobs {
+        let mut has_gap = false;
+        for file in app.output_file_names(&job.name) {
+            if let Some(problem) = file_problem(files_by_name.get(&file)) {
+                has_gap = true;
+                gaps.push(OutputGap {
+                    job_name: job.name.clone(), job_id: job.id.clone(), file, problem,
+                })
+            }
+        }
+        if has_gap {
+            rerun_names.push(job.name.clone())
+        }
+    }
+    let work_dir_string = conf.workspace.work_dir_fixed()?;
+    let work_dir = Path::new(&work_dir_string);
+    fs::create_dir_all(work_dir)?;
+    let rerun_file = work_dir.join(rerun_file_name(app));
+    let mut rerun_content = rerun_names.join("\n");
+    if !rerun_content.is_empty() {
+        rerun_content.push('\n')
+    }
+    fs::write(&rerun_file, rerun_content)?;
+    print_rows(format, &gaps, || {
+        for gap in &gaps {
+            println!("{} ({}): {} is {}", gap.job_name, gap.job_id, gap.file, gap.problem)
+        }
+        println!("{} of {} done {} jobs have complete outputs in {}.",
+                 done_jobs.len() - rerun_names.len(), done_jobs.len(), app.name, out_dir);
+        if !rerun_names.is_empty() {
+            println!("Wrote {} job names to {}. Rerun them with 'udix {} run --names {}'.",
+                     rerun_names.len(), rerun_file.to_string_lossy(), app.name,
+                     rerun_file.to_string_lossy());
+        }
+    })?;
+    if rerun_names.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::from(format!("{} done {} jobs have incomplete outputs.", rerun_names.len(),
+                                app.name)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::platform::FileInfo;
+    use super::file_problem;
+
+    fn file(size: u64, state: &str) -> FileInfo {
+        FileInfo {
+            name: "vcfs2bed_c1_b0.bed".to_string(),
+            id: format!("file-{}-{}", size, state),
+            project: "project-fake".to_string(),
+            size,
+            state: state.to_string(),
+            modified: 0,
+        }
+    }
+
+    #[test]
+    fn classify_output_file_problems() {
+        assert_eq!(file_problem(None).as_deref(), Some("missing"));
+        assert_eq!(file_problem(Some(&vec![file(10, "closed")])), None);
+        assert_eq!(file_problem(Some(&vec![file(0, "closed")])).as_deref(), Some("empty"));
+        assert_eq!(file_problem(Some(&vec![file(0, "open"), file(5, "closing")])).as_deref(),
+                   Some("not closed, but open, closing"));
+        assert_eq!(file_problem(Some(&vec![file(0, "open"), file(10, "closed")])), None);
+    }
+}